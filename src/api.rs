@@ -0,0 +1,180 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+use crate::stats::Userboard;
+
+// Client of the contest API.
+//
+// - Base URL: --base-url, $ICFP2023_API_URL, or DEFAULT_BASE_URL.
+// - Token: $ICFP2023_API_TOKEN, or the content of --token-file or
+//   $ICFP2023_API_TOKEN_FILE.
+
+pub const DEFAULT_BASE_URL: &str = "https://api.icfpcontest.com";
+
+// Interval between submissions of submit-best.
+const SUBMIT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// The API wraps results in {"Success": ...} or {"Failure": ...}.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Response<T> {
+    Success(T),
+    Failure(String),
+}
+
+impl<T> Response<T> {
+    fn into_result(self) -> Result<T> {
+        match self {
+            Response::Success(t) => Ok(t),
+            Response::Failure(e) => bail!("api failure: {e}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Submit {
+    pub problem_id: ProblemId,
+    /// The solution in JSON.
+    pub contents: String,
+}
+
+pub struct Client {
+    base_url: String,
+    token: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Client {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.trim().to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(60))
+                .build(),
+        }
+    }
+
+    /// Falls back to the environment variables for what is not given.
+    pub fn from_env(base_url: Option<String>, token_file: Option<PathBuf>) -> Result<Self> {
+        let base_url = base_url
+            .or_else(|| std::env::var("ICFP2023_API_URL").ok())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let read = |path: PathBuf| {
+            std::fs::read_to_string(&path).with_context(|| format!("read token from {path:?}"))
+        };
+        // A file given on the command line wins over the environment.
+        let token = match token_file {
+            Some(path) => read(path)?,
+            None => match std::env::var("ICFP2023_API_TOKEN") {
+                Ok(token) => token,
+                Err(_) => match std::env::var_os("ICFP2023_API_TOKEN_FILE") {
+                    Some(path) => read(PathBuf::from(path))?,
+                    None => {
+                        bail!("set ICFP2023_API_TOKEN, ICFP2023_API_TOKEN_FILE or --token-file")
+                    }
+                },
+            },
+        };
+        Ok(Client::new(&base_url, &token))
+    }
+
+    fn get(&self, path: &str) -> ureq::Request {
+        self.agent
+            .get(&format!("{}{path}", self.base_url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    fn post(&self, path: &str) -> ureq::Request {
+        self.agent
+            .post(&format!("{}{path}", self.base_url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    // The body of error responses says more than the status code.
+    fn call(request: ureq::Request, body: Option<&Submit>) -> Result<String> {
+        let result = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        match result {
+            Ok(response) => Ok(response.into_string()?),
+            Err(ureq::Error::Status(code, response)) => {
+                bail!(
+                    "http {code}: {}",
+                    response.into_string().unwrap_or_default()
+                )
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the submission id.
+    pub fn submit(&self, id: ProblemId, solution: &Solution) -> Result<String> {
+        let body = Submit {
+            problem_id: id,
+            contents: serde_json::to_string(solution)?,
+        };
+        let response = Client::call(self.post("/submission"), Some(&body))?;
+        if let Ok(response) = serde_json::from_str::<Response<String>>(&response) {
+            return response.into_result();
+        }
+        // The id comes as a bare JSON string.
+        Ok(serde_json::from_str(&response).unwrap_or(response))
+    }
+
+    pub fn submissions(&self, offset: usize, limit: usize) -> Result<serde_json::Value> {
+        let response = Client::call(
+            self.get(&format!("/submissions?offset={offset}&limit={limit}")),
+            None,
+        )?;
+        serde_json::from_str::<Response<_>>(&response)?.into_result()
+    }
+
+    fn fetch_userboard(&self) -> Result<(Userboard, String)> {
+        let response = Client::call(self.get("/userboard"), None)?;
+        let userboard = serde_json::from_str(&response)
+            .with_context(|| format!("unexpected userboard: {response}"))?;
+        Ok((userboard, response))
+    }
+
+    pub fn userboard(&self) -> Result<Userboard> {
+        Ok(self.fetch_userboard()?.0)
+    }
+
+    /// Fetches the userboard and saves it as stats/userboard.json.
+    pub fn refresh_userboard(&self) -> Result<Userboard> {
+        let (userboard, response) = self.fetch_userboard()?;
+        write_to("stats/userboard.json", &response)?;
+        Ok(userboard)
+    }
+
+    /// Submits solution/best/{id}.json of `ids` unless it is the same as
+    /// solution/submission/{id}.json, which records what was submitted.
+    /// Returns the number of submissions.
+    pub fn submit_best(&self, ids: &[ProblemId]) -> Result<usize> {
+        let mut nsubmit = 0;
+        for &id in ids {
+            let best = format!("solution/best/{id}.json");
+            let Ok(content) = read_from(&best) else {
+                continue;
+            };
+            let submitted = format!("solution/submission/{id}.json");
+            if read_from(&submitted).is_ok_and(|s| s == content) {
+                println!("Skipping {best}");
+                continue;
+            }
+            if nsubmit > 0 {
+                std::thread::sleep(SUBMIT_INTERVAL);
+            }
+            println!("Submitting {best}...");
+            let solution =
+                serde_json::from_str(&content).with_context(|| format!("parse {best}"))?;
+            let submission_id = self.submit(id, &solution)?;
+            println!("submission_id: {submission_id}");
+            write_to(&submitted, &content)?;
+            nsubmit += 1;
+        }
+        Ok(nsubmit)
+    }
+}
@@ -0,0 +1,257 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::progress::Progress;
+use crate::solver::{seeded_rng, SEED};
+use crate::solver_sa::{run_sa, End, LocalState, Params, SolverSa};
+use crate::trace::Trace;
+use rand::Rng;
+
+// Benchmark suite. `run` measures each problem of the suite, and compares the
+// result with a baseline saved by an earlier run, e.g. stats/bench.json.
+
+pub const BASELINE: &str = "stats/bench.json";
+
+/// Small, large and V2-with-pillars problems.
+pub const SUITE: [ProblemId; 7] = [
+    42, // 5 musicians, 100 attendees
+    22, // 16 musicians, 400 attendees
+    8,  // 124 musicians, 5000 attendees
+    1,  // 1059 musicians, 1000 attendees
+    60, // V2: 88 musicians, 47 pillars
+    73, // V2: 436 musicians, 858 pillars
+    61, // V2: 844 musicians, 149 pillars
+];
+
+const NITER: usize = 20_000;
+// Each timing repeats for at least this long.
+const BUDGET: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Measure {
+    pub id: ProblemId,
+    pub local_state_new_secs: f64,
+    pub moves_per_sec: f64,
+    pub swaps_per_sec: f64,
+    /// Heap memory of `LocalState`.
+    pub memory_bytes: usize,
+    /// Score after `niter` iterations of SA from the initial solution.
+    pub score: Score,
+    pub niter: usize,
+}
+
+// Operations per second. `f` returns how many operations it did.
+fn throughput(mut f: impl FnMut() -> usize) -> f64 {
+    let timer = std::time::Instant::now();
+    let mut n = 0;
+    while timer.elapsed() < BUDGET {
+        n += f();
+    }
+    n as f64 / timer.elapsed().as_secs_f64()
+}
+
+pub fn measure(id: ProblemId) -> Result<Measure> {
+    let problem = Problem::new(id)?;
+    let solution = SolverSa::initial_solution(&problem);
+    let nm = problem.musicians.len();
+
+    let local_state_new_secs = 1.0
+        / throughput(|| {
+            LocalState::new(&problem, id, id.into(), &solution);
+            1
+        });
+    let mut st = LocalState::new(&problem, id, id.into(), &solution);
+    let memory_bytes = st.heap_bytes();
+
+    // To a random point on the stage and back.
+    let mut rng = seeded_rng(SEED);
+    let moves_per_sec = throughput(|| {
+        let i = rng.gen_range(0..nm);
+        let p0 = st.place[i];
+        let p = problem.random_point_on_stage(&mut rng);
+        if st.collides(i, p) {
+            return 0;
+        }
+        st.do_move(i, p);
+        st.do_move(i, p0);
+        2
+    });
+
+    // Skip self-swaps as `run_sa` does. On V2 they make the closeness factor
+    // infinite.
+    let swaps_per_sec = throughput(|| {
+        let a = rng.gen_range(0..nm);
+        let b = rng.gen_range(0..nm);
+        if a == b {
+            return 0;
+        }
+        st.do_swap(a, b);
+        1
+    });
+    drop(st);
+
+    let (score, _, niter) = run_sa(
+        &mut Trace::hidden(),
+        &mut seeded_rng(SEED),
+        &problem,
+        id,
+        id.into(),
+        &solution,
+        &Params::default(),
+        &End::MaxIteration(NITER),
+        false,
+        &Progress::hidden(),
+    )?;
+
+    Ok(Measure {
+        id,
+        local_state_new_secs,
+        moves_per_sec,
+        swaps_per_sec,
+        memory_bytes,
+        score,
+        niter,
+    })
+}
+
+/// What got worse than the baseline by more than `tolerance`, relatively.
+/// Scores are deterministic, so any drop is a regression.
+fn regressions(measure: &Measure, baseline: &Measure, tolerance: f64) -> Vec<String> {
+    let mut regressions = vec![];
+    let mut check = |name: &str, new: f64, old: f64, higher_is_better: bool| {
+        let change = (new - old) / old.abs().max(f64::MIN_POSITIVE);
+        let worse = if higher_is_better { -change } else { change };
+        if worse > tolerance {
+            regressions.push(format!(
+                "{name}: {old:.4e} -> {new:.4e} ({:+.1}%)",
+                change * 100.0
+            ));
+        }
+    };
+    check(
+        "local_state_new_secs",
+        measure.local_state_new_secs,
+        baseline.local_state_new_secs,
+        false,
+    );
+    check(
+        "moves_per_sec",
+        measure.moves_per_sec,
+        baseline.moves_per_sec,
+        true,
+    );
+    check(
+        "swaps_per_sec",
+        measure.swaps_per_sec,
+        baseline.swaps_per_sec,
+        true,
+    );
+    check(
+        "memory_bytes",
+        measure.memory_bytes as f64,
+        baseline.memory_bytes as f64,
+        false,
+    );
+    if measure.niter == baseline.niter && measure.score < baseline.score {
+        regressions.push(format!("score: {} -> {}", baseline.score, measure.score));
+    }
+    regressions
+}
+
+// `baseline` with the measures of `measures` replaced or added, by id.
+fn merge(mut baseline: Vec<Measure>, measures: Vec<Measure>) -> Vec<Measure> {
+    for m in measures {
+        match baseline.iter_mut().find(|b| b.id == m.id) {
+            Some(b) => *b = m,
+            None => baseline.push(m),
+        }
+    }
+    baseline
+}
+
+/// Measures `ids` one by one, so that they don't disturb each other, and
+/// compares them with `baseline_path` if it exists. If `save`, merges the
+/// measures into the baseline, unless there are regressions and not `force`.
+/// Returns the regressions.
+pub fn run(
+    ids: &[ProblemId],
+    baseline_path: &Path,
+    tolerance: f64,
+    save: bool,
+    force: bool,
+) -> Result<Vec<(ProblemId, String)>> {
+    let baseline: Vec<Measure> = match std::fs::read_to_string(baseline_path) {
+        Ok(s) => serde_json::from_str(&s).with_context(|| format!("parse {baseline_path:?}"))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e).with_context(|| format!("read {baseline_path:?}")),
+    };
+
+    let mut measures = vec![];
+    let mut regressions = vec![];
+    println!("# id state_new(ms) moves/s swaps/s memory(KiB) score");
+    for &id in ids {
+        let m = measure(id)?;
+        println!(
+            "{} {:.3} {:.0} {:.0} {} {:.0}",
+            m.id,
+            m.local_state_new_secs * 1000.0,
+            m.moves_per_sec,
+            m.swaps_per_sec,
+            m.memory_bytes / 1024,
+            m.score
+        );
+        if let Some(old) = baseline.iter().find(|b| b.id == id) {
+            for r in self::regressions(&m, old, tolerance) {
+                println!("# {id}: regression: {r}");
+                regressions.push((id, r));
+            }
+        }
+        measures.push(m);
+    }
+    if baseline.is_empty() {
+        println!("# no baseline: {}", baseline_path.display());
+    }
+
+    if save && !regressions.is_empty() && !force {
+        println!(
+            "# not saved: {} regressions. --force to save anyway",
+            regressions.len()
+        );
+    } else if save {
+        if let Some(parent) = baseline_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let merged = merge(baseline, measures);
+        std::fs::write(baseline_path, serde_json::to_string_pretty(&merged)?)?;
+        println!("# saved {}", baseline_path.display());
+    }
+    Ok(regressions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_regressions() -> Result<()> {
+        let m = measure(42)?;
+        assert_eq!(m.niter, NITER);
+        assert!(regressions(&m, &m, 0.1).is_empty());
+
+        let mut worse = m.clone();
+        worse.moves_per_sec /= 2.0;
+        worse.score -= 1.0;
+        let r = regressions(&worse, &m, 0.1);
+        assert_eq!(r.len(), 2, "{r:?}");
+
+        // Saving 42 keeps the baseline of 22.
+        let other = Measure {
+            id: 22,
+            ..m.clone()
+        };
+        let merged = merge(vec![m.clone(), other], vec![worse]);
+        assert_eq!(merged.iter().map(|b| b.id).collect::<Vec<_>>(), [42, 22]);
+        assert_eq!(merged[0].score, m.score - 1.0);
+        Ok(())
+    }
+}
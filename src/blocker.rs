@@ -0,0 +1,180 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+use crate::solver::*;
+use crate::solver_sa::LocalState;
+
+// Post-processing which turns musicians with a net negative impact into
+// silent blockers, standing where they hide musicians from attendees who
+// dislike them.
+
+// Candidate positions screened by `blocking_gain` for each blocker.
+const NUM_CANDIDATES: usize = 200;
+
+// Candidates verified by actually moving the blocker.
+const NUM_VERIFIED: usize = 5;
+
+// Musicians which can't earn a positive score where they stand.
+fn blockers(st: &LocalState) -> Vec<usize> {
+    (0..st.place.len())
+        .filter(|&i| st.contribution(i) <= 0.0)
+        .collect()
+}
+
+fn candidates(st: &LocalState, rng: &mut StdRng, b: usize) -> Vec<Point> {
+    let p0 = st.place[b];
+    (0..NUM_CANDIDATES)
+        .map(|n| {
+            if n % 2 == 0 {
+                st.problem.random_point_on_stage(rng)
+            } else {
+                let dist = 40.0 * rng.gen_range(0.0f64..1.0).powi(2);
+                let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
+                Point::new(p0.x + dist * angle.cos(), p0.y + dist * angle.sin())
+            }
+        })
+        .filter(|p| !st.collides(b, *p))
+        .collect()
+}
+
+pub fn optimize_blockers(
+    rng: &mut StdRng,
+    problem: &Problem,
+    problem_id: ProblemId,
+    spec: Spec,
+    solution: &Solution,
+    rounds: usize,
+) -> (Score, Solution) {
+    let mut st = LocalState::new(problem, problem_id, spec, solution);
+    st.set_volumes(solution.volumes.clone());
+
+    let blockers = blockers(&st);
+    for &b in &blockers {
+        st.do_volume(b, 0.0);
+    }
+    info!(
+        "problem_id: {problem_id}, blockers: {}, score: {}",
+        blockers.len(),
+        st.score
+    );
+
+    for round in 0..rounds {
+        let mut nimprove = 0;
+        for &b in &blockers {
+            let mut gains = candidates(&st, rng, b)
+                .into_iter()
+                .map(|p| (st.blocking_gain(b, p), p))
+                .filter(|(gain, _)| *gain > 0.0)
+                .collect::<Vec<_>>();
+            gains.sort_by_key(|(gain, _)| std::cmp::Reverse(OrderedFloat(*gain)));
+
+            let (sc, p0) = (st.score, st.place[b]);
+            let mut best = (sc, p0);
+            for (_, p) in gains.into_iter().take(NUM_VERIFIED) {
+                st.do_move(b, p);
+                if st.score > best.0 {
+                    best = (st.score, p);
+                }
+            }
+            st.do_move(b, best.1);
+            if best.0 > sc {
+                nimprove += 1;
+            }
+        }
+        info!("round: {round}, improved: {nimprove}, score: {}", st.score);
+        if nimprove == 0 {
+            break;
+        }
+    }
+
+    (st.score, st.to_solution())
+}
+
+pub struct BlockerOptimizer {
+    problem_id: ProblemId,
+    problem: Problem,
+    rounds: usize,
+    solution: Solution,
+}
+
+impl BlockerOptimizer {
+    pub fn new(problem_id: ProblemId, rounds: usize, solution: Solution) -> Result<Self> {
+        let problem = Problem::new(problem_id)?;
+        Ok(Self {
+            problem_id,
+            problem,
+            rounds,
+            solution,
+        })
+    }
+}
+
+impl Solver for BlockerOptimizer {
+    fn problem_id(&self) -> ProblemId {
+        self.problem_id
+    }
+
+    fn name(&self) -> String {
+        format!("blocker-rounds-{}", self.rounds)
+    }
+
+    fn params(&self) -> serde_json::Value {
+        serde_json::json!({ "rounds": self.rounds })
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(SEED)
+    }
+
+    fn parent(&self) -> Option<&Solution> {
+        Some(&self.solution)
+    }
+
+    fn solve(&mut self) -> Result<Solved> {
+        let mut rng = seeded_rng(SEED);
+        let (score, solution) = optimize_blockers(
+            &mut rng,
+            &self.problem,
+            self.problem_id,
+            self.problem_id.into(),
+            &self.solution,
+            self.rounds,
+        );
+        let Solution {
+            placements,
+            volumes,
+        } = solution;
+        Ok(Solved {
+            problem_id: self.problem_id,
+            solver_name: self.name(),
+            score,
+            placements,
+            volumes,
+            niter: None,
+            meta: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blockers_never_hurt() -> Result<()> {
+        let id = 45;
+        let problem = Problem::new(id)?;
+        let best = Solution::best(id)?;
+        let before = crate::solver_sa::score(&problem, id, id.into(), &best);
+        let mut rng = SeedableRng::from_seed([0; 32]);
+        let (after, solution) = optimize_blockers(&mut rng, &problem, id, id.into(), &best, 1);
+        assert!(after >= before);
+        assert_relative_eq!(
+            after,
+            crate::solver_sa::score(&problem, id, id.into(), &solution),
+            max_relative = 1e-6
+        );
+        Ok(())
+    }
+}
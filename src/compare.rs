@@ -0,0 +1,149 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+use crate::solver_sa::Scorer;
+
+// Compares two solution directories, each with {id}.json, e.g. solution/best
+// and solution/submission.
+
+pub struct Versus {
+    pub id: ProblemId,
+    /// None if the solution is missing or invalid.
+    pub a: Option<Score>,
+    pub b: Option<Score>,
+}
+
+impl Versus {
+    /// b - a.
+    pub fn delta(&self) -> Option<Score> {
+        Some(self.b? - self.a?)
+    }
+
+    /// (b - a) / |a|.
+    pub fn relative(&self) -> Option<f64> {
+        let a = self.a?;
+        Some((self.b? - a) / a.abs().max(1.0))
+    }
+}
+
+fn score(id: ProblemId, path: &Path, scorer: Scorer) -> Result<Option<Score>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let problem = Problem::new(id)?;
+    let solution = Solution::from(path)?;
+    solution.validate(&problem)?;
+    Ok(Some(scorer.score(&problem, id, id.into(), &solution)))
+}
+
+/// Scores the solutions of both directories in parallel.
+pub fn compare(a: &Path, b: &Path, scorer: Scorer) -> Vec<Versus> {
+    use rayon::prelude::*;
+
+    problem_ids()
+        .into_par_iter()
+        .map(|id| {
+            let score = |dir: &Path| {
+                let path = dir.join(format!("{id}.json"));
+                score(id, &path, scorer).unwrap_or_else(|e| {
+                    warn!("{}: {e:#}", path.display());
+                    None
+                })
+            };
+            Versus {
+                id,
+                a: score(a),
+                b: score(b),
+            }
+        })
+        .collect()
+}
+
+fn fmt<T>(value: Option<T>, f: impl Fn(T) -> String) -> String {
+    value.map_or("-".to_string(), f)
+}
+
+pub fn print(a: &Path, b: &Path, versus: &[Versus]) {
+    println!("# a: {}, b: {}", a.display(), b.display());
+    println!("# id a b delta relative");
+    for v in versus {
+        println!(
+            "{} {} {} {} {}",
+            v.id,
+            fmt(v.a, |s| format!("{s:.0}")),
+            fmt(v.b, |s| format!("{s:.0}")),
+            fmt(v.delta(), |d| format!("{d:+.0}")),
+            fmt(v.relative(), |r| format!("{:+.2}%", r * 100.0)),
+        );
+    }
+    let count = |f: fn(Score) -> bool| {
+        versus
+            .iter()
+            .flat_map(|v| v.delta())
+            .filter(|&d| f(d))
+            .count()
+    };
+    let total = |f: fn(&Versus) -> Option<Score>| versus.iter().flat_map(f).fold(0.0, |t, s| t + s);
+    let (total_a, total_b) = (total(|v| v.a), total(|v| v.b));
+    println!(
+        "# b wins: {}, loses: {}, ties: {}, total: {total_a:.0} -> {total_b:.0} ({:+.0})",
+        count(|d| d > 0.0),
+        count(|d| d < 0.0),
+        count(|d| d == 0.0),
+        total_b - total_a
+    );
+}
+
+pub fn write_csv(path: &Path, versus: &[Versus]) -> Result<()> {
+    let mut csv = "id,a,b,delta,relative\n".to_string();
+    for v in versus {
+        // Empty if missing.
+        let fmt = |value: Option<f64>| value.map_or(String::new(), |x| x.to_string());
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            v.id,
+            fmt(v.a),
+            fmt(v.b),
+            fmt(v.delta()),
+            fmt(v.relative())
+        );
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, csv).with_context(|| format!("write {path:?}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver_sa::SolverSa;
+
+    #[test]
+    fn compare_dirs() -> Result<()> {
+        let id = 42;
+        let dir = std::env::temp_dir().join(format!("compare-{}", std::process::id()));
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        std::fs::create_dir_all(&a)?;
+        std::fs::create_dir_all(&b)?;
+        let problem = Problem::new(id)?;
+        let initial = SolverSa::initial_solution(&problem);
+        std::fs::write(a.join("42.json"), serde_json::to_string(&initial)?)?;
+        std::fs::write(b.join("42.json"), read_from("solution/best/42.json")?)?;
+        std::fs::write(b.join("1.json"), "{}")?;
+
+        let versus = compare(&a, &b, Scorer::Exact);
+        let v = versus.iter().find(|v| v.id == id).unwrap();
+        assert!(v.delta().unwrap() > 0.0);
+        let v = versus.iter().find(|v| v.id == 1).unwrap();
+        assert_eq!((v.a, v.b), (None, None));
+
+        write_csv(&dir.join("compare.csv"), &versus)?;
+        let csv = std::fs::read_to_string(dir.join("compare.csv"))?;
+        assert!(csv.lines().any(|line| line == "1,,,,"));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
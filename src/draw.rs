@@ -15,11 +15,15 @@ pub fn draw_svg(
     solution: Option<&Solution>,
     out_path: impl AsRef<Path>,
 ) -> Result<()> {
+    std::fs::create_dir_all(out_path.as_ref().parent().unwrap())?;
+    svg::save(out_path, &svg_document(problem, solution))?;
+    Ok(())
+}
+
+pub fn svg_document(problem: &Problem, solution: Option<&Solution>) -> svg::Document {
     use svg::node::element::*;
     use svg::*;
 
-    std::fs::create_dir_all(out_path.as_ref().parent().unwrap())?;
-
     let mut document =
         Document::new().set("viewBox", (0, 0, problem.room_width, problem.room_height));
 
@@ -93,9 +97,7 @@ pub fn draw_svg(
         }
     }
 
-    svg::save(out_path, &document)?;
-
-    Ok(())
+    document
 }
 
 pub fn draw_problem(id: ProblemId, out_path: impl AsRef<Path>) -> Result<()> {
@@ -114,5 +116,5 @@ pub fn draw_solution_file(
 
 pub fn draw_solution(id: ProblemId, solution: &Solution, out_path: impl AsRef<Path>) -> Result<()> {
     let problem = Problem::new(id)?;
-    draw_svg(&problem, Some(&solution), out_path)
+    draw_svg(&problem, Some(solution), out_path)
 }
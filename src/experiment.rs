@@ -0,0 +1,468 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solver::{Solved, Solver};
+use crate::solver_lns::SolverLns;
+use crate::solver_sa::{End, Params, SolverSa};
+use crate::trace;
+
+use serde_json::{Map, Value};
+
+// Parameter sweeps. An experiment, in JSON, lists problems, seeds, and solvers
+// with grids of parameters:
+//
+// {
+//   "name": "temp0",
+//   "problems": [42, 60],
+//   "seeds": [0, 1, 2],
+//   "solvers": [
+//     {"solver": "sa", "grid": {"temp0": [10, 100, null], "swap_prob": [0.1, 0.2]}},
+//     {"solver": "lns", "grid": {"k": [3, 5], "end": ["iter-10000"]}}
+//   ]
+// }
+//
+// Every combination runs once. Results go to
+// stats/experiment/{name}/{run id}/: runs.csv has a row per run, and
+// summary.csv the mean, the standard deviation and the best per configuration
+// and problem.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Experiment {
+    pub name: String,
+    pub problems: Vec<ProblemId>,
+    #[serde(default = "default_seeds")]
+    pub seeds: Vec<u64>,
+    pub solvers: Vec<SolverSpec>,
+}
+
+fn default_seeds() -> Vec<u64> {
+    vec![crate::solver::SEED]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SolverSpec {
+    /// "sa" or "lns".
+    pub solver: String,
+    /// Values to try per parameter. Missing parameters take their defaults.
+    #[serde(default)]
+    pub grid: BTreeMap<String, Vec<Value>>,
+}
+
+/// A solver with one value per parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub solver: String,
+    pub params: Map<String, Value>,
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.solver)?;
+        for (k, v) in &self.params {
+            write!(f, " {k}={v}")?;
+        }
+        Ok(())
+    }
+}
+
+impl SolverSpec {
+    /// The cartesian product of the grid.
+    pub fn configs(&self) -> Vec<Config> {
+        let mut configs = vec![Map::new()];
+        for (k, values) in &self.grid {
+            configs = configs
+                .into_iter()
+                .flat_map(|params| {
+                    values.iter().map(move |v| {
+                        let mut params = params.clone();
+                        params.insert(k.clone(), v.clone());
+                        params
+                    })
+                })
+                .collect();
+        }
+        configs
+            .into_iter()
+            .map(|params| Config {
+                solver: self.solver.clone(),
+                params,
+            })
+            .collect()
+    }
+}
+
+fn param<T: serde::de::DeserializeOwned>(
+    params: &Map<String, Value>,
+    key: &str,
+) -> Result<Option<T>> {
+    params
+        .get(key)
+        .map(|v| serde_json::from_value(v.clone()).with_context(|| format!("{key}: {v}")))
+        .transpose()
+}
+
+fn end(params: &Map<String, Value>) -> Result<End> {
+    param::<String>(params, "end")?
+        .unwrap_or_else(|| "iter-100000".to_string())
+        .parse()
+}
+
+// null scales with the initial score, which is also the default, as with the
+// lns command.
+fn lns_temp0(params: &Map<String, Value>) -> Result<Option<f64>> {
+    Ok(param::<Option<f64>>(params, "temp0")?.flatten())
+}
+
+impl Config {
+    fn check_keys(&self, known: &[&str]) -> Result<()> {
+        for k in self.params.keys() {
+            ensure!(
+                known.contains(&k.as_str()),
+                "{}: unknown parameter: {k}",
+                self.solver
+            );
+        }
+        Ok(())
+    }
+
+    pub fn solver(&self, id: ProblemId, seed: u64) -> Result<Box<dyn Solver + Send>> {
+        let p = &self.params;
+        Ok(match self.solver.as_str() {
+            "sa" => {
+                // The rest are `Params`.
+                let mut params = p.clone();
+                params.remove("end");
+                params.remove("volume_search");
+                let params: Params = serde_json::from_value(Value::Object(params))
+                    .with_context(|| format!("sa: {self}"))?;
+                let mut solver = SolverSa::new(
+                    id,
+                    params.temp0,
+                    end(p)?,
+                    param(p, "volume_search")?.unwrap_or(false),
+                    None,
+                )?;
+                solver.set_params(params)?;
+                solver.hide_trace();
+                solver.set_seed(seed);
+                Box::new(solver)
+            }
+            "lns" => {
+                self.check_keys(&["k", "temp0", "end"])?;
+                let mut solver = SolverLns::new(
+                    id,
+                    param(p, "k")?.unwrap_or(3),
+                    lns_temp0(p)?,
+                    end(p)?,
+                    None,
+                )?;
+                solver.hide_drawing();
+                solver.set_seed(seed);
+                Box::new(solver)
+            }
+            solver => bail!("unknown solver: {solver}"),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Run {
+    pub problem_id: ProblemId,
+    pub class: String,
+    pub config: String,
+    pub seed: u64,
+    pub score: Score,
+    pub runtime_secs: f64,
+    pub niter: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub problem_id: ProblemId,
+    pub class: String,
+    pub config: String,
+    pub n: usize,
+    pub mean: Score,
+    /// Sample standard deviation. 0 for a single run.
+    pub std: Score,
+    pub best: Score,
+}
+
+pub fn summarize(runs: &[Run]) -> Vec<Summary> {
+    let mut groups: BTreeMap<(ProblemId, &str), Vec<&Run>> = BTreeMap::new();
+    for r in runs {
+        groups
+            .entry((r.problem_id, r.config.as_str()))
+            .or_default()
+            .push(r);
+    }
+    groups
+        .into_iter()
+        .map(|((problem_id, config), runs)| {
+            let n = runs.len();
+            let mean = runs.iter().map(|r| r.score).sum::<Score>() / n as Score;
+            let var = if n > 1 {
+                runs.iter().map(|r| (r.score - mean).powi(2)).sum::<Score>() / (n - 1) as Score
+            } else {
+                0.0
+            };
+            Summary {
+                problem_id,
+                class: runs[0].class.clone(),
+                config: config.to_string(),
+                n,
+                mean,
+                std: var.sqrt(),
+                best: runs
+                    .iter()
+                    .map(|r| r.score)
+                    .fold(f64::NEG_INFINITY, f64::max),
+            }
+        })
+        .collect()
+}
+
+/// Per class, how many problems each configuration has the highest mean on.
+/// Means within `tune::TIE` of the highest, relatively, win too.
+pub fn wins(summaries: &[Summary]) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut highest: BTreeMap<ProblemId, Score> = BTreeMap::new();
+    for s in summaries {
+        let highest = highest.entry(s.problem_id).or_insert(s.mean);
+        *highest = highest.max(s.mean);
+    }
+    let mut wins: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    for s in summaries {
+        let highest = highest[&s.problem_id];
+        if (highest - s.mean) / highest.abs().max(1.0) > crate::tune::TIE {
+            continue;
+        }
+        *wins
+            .entry(s.class.clone())
+            .or_default()
+            .entry(s.config.clone())
+            .or_default() += 1;
+    }
+    wins
+}
+
+fn run_one(
+    config: &Config,
+    id: ProblemId,
+    class: Class,
+    seed: u64,
+    save_best: bool,
+) -> Result<Run> {
+    let mut solver = config.solver(id, seed)?;
+    let start = std::time::Instant::now();
+    let mut solved: Solved = solver.solve()?;
+    let runtime = start.elapsed();
+    if save_best {
+        solved.set_meta(solver.as_ref(), runtime)?;
+        solved.save_best_if()?;
+    }
+    Ok(Run {
+        problem_id: id,
+        class: class.to_string(),
+        config: config.to_string(),
+        seed,
+        score: solved.score,
+        runtime_secs: runtime.as_secs_f64(),
+        niter: solved.niter,
+    })
+}
+
+const RUNS_HEADER: &str = "problem_id,class,config,seed,score,runtime_secs,niter\n";
+
+fn run_row(r: &Run) -> String {
+    format!(
+        "{},{},\"{}\",{},{},{},{}\n",
+        r.problem_id,
+        r.class,
+        r.config.replace('"', "\"\""),
+        r.seed,
+        r.score,
+        r.runtime_secs,
+        r.niter.map_or(String::new(), |n| n.to_string())
+    )
+}
+
+fn write_summary_csv(dir: &Path, summaries: &[Summary]) -> Result<()> {
+    let mut csv = "problem_id,class,config,n,mean,std,best\n".to_string();
+    for s in summaries {
+        csv += &format!(
+            "{},{},\"{}\",{},{},{},{}\n",
+            s.problem_id,
+            s.class,
+            s.config.replace('"', "\"\""),
+            s.n,
+            s.mean,
+            s.std,
+            s.best
+        );
+    }
+    std::fs::write(dir.join("summary.csv"), csv)?;
+    Ok(())
+}
+
+/// Runs every combination of the spec on `jobs` threads. Promotes results to
+/// solution/best only if `save_best`. Returns the output directory. runs.csv
+/// gets each run as it finishes, and a failed run doesn't stop the others.
+pub fn run(spec: &Experiment, jobs: usize, save_best: bool) -> Result<PathBuf> {
+    use rayon::prelude::*;
+    use std::io::Write as _;
+
+    ensure!(!spec.problems.is_empty(), "no problems");
+    ensure!(!spec.seeds.is_empty(), "no seeds");
+    let configs = spec
+        .solvers
+        .iter()
+        .flat_map(|s| s.configs())
+        .collect::<Vec<_>>();
+    ensure!(!configs.is_empty(), "no solver configs");
+    let classes = spec
+        .problems
+        .iter()
+        .map(|&id| Ok((id, Class::new(id.into(), &Problem::new(id)?))))
+        .collect::<Result<HashMap<_, _>>>()?;
+    // Fail on bad parameters before running anything.
+    for config in &configs {
+        config.solver(spec.problems[0], 0)?;
+    }
+
+    let tasks = configs
+        .iter()
+        .flat_map(|config| {
+            spec.problems
+                .iter()
+                .flat_map(move |&id| spec.seeds.iter().map(move |&seed| (config, id, seed)))
+        })
+        .collect::<Vec<_>>();
+    let ntask = tasks.len();
+    let ndone = std::sync::atomic::AtomicUsize::new(0);
+    println!("# {} configs, {ntask} runs", configs.len());
+
+    let dir = project_path(format!(
+        "stats/experiment/{}/{}",
+        spec.name,
+        trace::run_id()
+    ));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("spec.json"), serde_json::to_string_pretty(spec)?)?;
+    let mut runs_csv = std::fs::File::create(dir.join("runs.csv"))?;
+    runs_csv.write_all(RUNS_HEADER.as_bytes())?;
+    let runs_csv = std::sync::Mutex::new(runs_csv);
+
+    let results = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()?
+        .install(|| {
+            tasks
+                .par_iter()
+                .map(|&(config, id, seed)| {
+                    let result = run_one(config, id, classes[&id], seed, save_best);
+                    let ndone = ndone.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                    match &result {
+                        Ok(run) => {
+                            println!(
+                                "[{ndone}/{ntask}] {id} seed={seed} {config}: {:.0}",
+                                run.score
+                            );
+                            runs_csv
+                                .lock()
+                                .unwrap()
+                                .write_all(run_row(run).as_bytes())?;
+                        }
+                        Err(e) => {
+                            println!("[{ndone}/{ntask}] {id} seed={seed} {config}: failed: {e:#}")
+                        }
+                    }
+                    Ok(result)
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+    let (runs, errors): (Vec<_>, Vec<_>) = results.into_iter().partition(|r| r.is_ok());
+    let runs = runs.into_iter().flatten().collect::<Vec<_>>();
+
+    let summaries = summarize(&runs);
+    write_summary_csv(&dir, &summaries)?;
+
+    println!("# id class config n mean std best");
+    for s in &summaries {
+        println!(
+            "{} {} [{}] {} {:.0} {:.0} {:.0}",
+            s.problem_id, s.class, s.config, s.n, s.mean, s.std, s.best
+        );
+    }
+    println!("# class config wins");
+    for (class, wins) in wins(&summaries) {
+        for (config, n) in wins {
+            println!("{class} [{config}] {n}");
+        }
+    }
+    ensure!(errors.is_empty(), "{} of {ntask} runs failed", errors.len());
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_configs() -> Result<()> {
+        let spec: SolverSpec = serde_json::from_str(
+            r#"{"solver": "sa", "grid": {"temp0": [10, null], "end": ["iter-1000", "iter-2000"]}}"#,
+        )?;
+        let configs = spec.configs();
+        assert_eq!(configs.len(), 4);
+        assert_eq!(configs[0].to_string(), "sa end=\"iter-1000\" temp0=10");
+        for config in &configs {
+            config.solver(42, 0)?;
+        }
+
+        let bad: SolverSpec = serde_json::from_str(r#"{"solver": "sa", "grid": {"k": [3]}}"#)?;
+        assert!(bad.configs()[0].solver(42, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn empty_experiment() -> Result<()> {
+        let solvers = r#"[{"solver": "sa", "grid": {"end": ["iter-10"]}}]"#;
+        for (problems, seeds) in [("[]", "[0]"), ("[42]", "[]")] {
+            let spec: Experiment = serde_json::from_str(&format!(
+                r#"{{"name": "empty", "problems": {problems}, "seeds": {seeds}, "solvers": {solvers}}}"#
+            ))?;
+            assert!(run(&spec, 1, false).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn summarize_runs() {
+        let run = |id, config: &str, score| Run {
+            problem_id: id,
+            class: "V1-small".to_string(),
+            config: config.to_string(),
+            seed: 0,
+            score,
+            runtime_secs: 0.0,
+            niter: None,
+        };
+        let runs = [
+            run(1, "a", 1.0),
+            run(1, "a", 3.0),
+            run(1, "b", 1.0),
+            run(2, "b", 5.0),
+            run(3, "a", 1e9),
+            run(3, "b", 1e9 - 0.1),
+        ];
+        let summaries = summarize(&runs);
+        assert_eq!(summaries.len(), 5);
+        assert_eq!(summaries[0].mean, 2.0);
+        assert_relative_eq!(summaries[0].std, 2.0f64.sqrt());
+        assert_eq!(summaries[0].best, 3.0);
+        let wins = wins(&summaries);
+        assert_eq!(wins["V1-small"]["a"], 2);
+        // Tied on 3.
+        assert_eq!(wins["V1-small"]["b"], 2);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::prelude::*;
+
+use crate::meta::{self, Meta};
+use crate::problem::ProblemId;
+
+use fd_lock::RwLock;
+
+// Append-only manifest of the solutions saved in solution/all, one JSON per
+// line.
+
+const PATH: &str = "solution/history.jsonl";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub path: String,
+    #[serde(flatten)]
+    pub meta: Meta,
+}
+
+fn open() -> Result<std::fs::File> {
+    let path = project_path(PATH);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?)
+}
+
+fn parse(content: &str) -> Result<Vec<Entry>> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+// Scored at least as high in no more time.
+fn dominates(a: &Meta, b: &Meta) -> bool {
+    a.problem_id == b.problem_id
+        && a.score >= b.score
+        && a.runtime_secs <= b.runtime_secs
+        && (a.score > b.score || a.runtime_secs < b.runtime_secs)
+}
+
+pub struct History(Vec<Entry>);
+
+impl History {
+    pub fn new() -> Result<Self> {
+        use std::io::Read as _;
+
+        if !project_path(PATH).exists() {
+            return Ok(History(vec![]));
+        }
+        let f = RwLock::new(open().context("history new")?);
+        let f = f.read()?;
+        let mut content = String::new();
+        f.deref().read_to_string(&mut content)?;
+        Ok(History(parse(&content)?))
+    }
+
+    pub fn append(entry: &Entry) -> Result<()> {
+        let mut f = RwLock::new(open().context("history append")?);
+        let mut f = f.write()?;
+        writeln!(f, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Runs for `id`, oldest first.
+    pub fn entries(&self, id: ProblemId) -> Vec<&Entry> {
+        let mut entries = self
+            .0
+            .iter()
+            .filter(|e| e.meta.problem_id == id)
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.meta.timestamp);
+        entries
+    }
+
+    /// The latest run which saved the solution whose hash is `hash`.
+    pub fn find(&self, hash: &str) -> Option<&Entry> {
+        self.0.iter().rev().find(|e| e.meta.solution_hash == hash)
+    }
+
+    pub fn print(&self, id: ProblemId) {
+        let entries = self.entries(id);
+        println!("# problem_id: {id}, runs: {}", entries.len());
+        println!("# time score exact_score runtime(s) niter solver gain parent");
+        let mut best = None;
+        for e in entries {
+            let m = &e.meta;
+            let gain = match best {
+                None => format!("{:+.0}", m.score),
+                Some(best) if m.score > best => format!("{:+.0}", m.score - best),
+                Some(_) => "-".to_string(),
+            };
+            best = Some(best.map_or(m.score, |best: Score| best.max(m.score)));
+            // The parent's path if a run saved it, its hash otherwise.
+            let parent = m.parent_hash.as_deref().map_or("-".to_string(), |hash| {
+                self.find(hash)
+                    .map_or(hash.to_string(), |parent| parent.path.clone())
+            });
+            println!(
+                "{} {:.0} {:.0} {:.1} {} {} {gain} {parent}",
+                meta::format_timestamp(m.timestamp),
+                m.score,
+                m.exact_score,
+                m.runtime_secs,
+                m.niter.map_or("-".to_string(), |n| n.to_string()),
+                m.solver_name,
+            );
+        }
+    }
+
+    /// Removes runs which another run of the same problem dominates, runs
+    /// whose files are gone, and runs whose files a later run overwrote.
+    /// Returns the removed runs.
+    pub fn prune(dry_run: bool) -> Result<Vec<Entry>> {
+        use std::io::{Read as _, Seek as _, SeekFrom};
+
+        let mut f = RwLock::new(open().context("history prune")?);
+        let mut f = f.write()?;
+        let mut content = String::new();
+        f.read_to_string(&mut content)?;
+        let entries = parse(&content)?;
+        let latest = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.path.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        let (present, removed): (Vec<_>, Vec<_>) =
+            entries.iter().cloned().enumerate().partition(|(i, e)| {
+                latest[e.path.as_str()] == *i && project_path(&e.path).exists()
+            });
+        let present = present.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+        let mut removed = removed.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+        let (kept, dominated): (Vec<_>, Vec<_>) = present
+            .iter()
+            .cloned()
+            .partition(|e| !present.iter().any(|o| dominates(&o.meta, &e.meta)));
+        removed.extend(dominated);
+        if dry_run {
+            return Ok(removed);
+        }
+
+        let kept_paths = kept.iter().map(|e| e.path.as_str()).collect::<HashSet<_>>();
+        for e in removed
+            .iter()
+            .filter(|e| !kept_paths.contains(e.path.as_str()))
+        {
+            for path in [e.path.clone(), Meta::path(&e.path)] {
+                let path = project_path(path);
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+
+        f.set_len(0)?;
+        f.seek(SeekFrom::Start(0))?;
+        for e in &kept {
+            writeln!(f, "{}", serde_json::to_string(e)?)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(score: Score, runtime_secs: f64) -> Meta {
+        Meta {
+            problem_id: 1,
+            problem_hash: String::new(),
+            solver_name: String::new(),
+            params: serde_json::Value::Null,
+            seed: None,
+            niter: None,
+            runtime_secs,
+            score,
+            exact_score: score,
+            solution_hash: String::new(),
+            parent_hash: None,
+            git_revision: None,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn dominates_test() {
+        assert!(dominates(&meta(2.0, 1.0), &meta(1.0, 1.0)));
+        assert!(dominates(&meta(1.0, 1.0), &meta(1.0, 2.0)));
+        assert!(!dominates(&meta(1.0, 1.0), &meta(1.0, 1.0)));
+        assert!(!dominates(&meta(2.0, 2.0), &meta(1.0, 1.0)));
+    }
+
+    #[test]
+    fn find_parent() {
+        let entry = |path: &str, hash: &str, parent_hash: Option<&str>| Entry {
+            path: path.to_string(),
+            meta: Meta {
+                solution_hash: hash.to_string(),
+                parent_hash: parent_hash.map(str::to_string),
+                ..meta(1.0, 1.0)
+            },
+        };
+        let history = History(vec![
+            entry("solution/all/a.json", "a", None),
+            entry("solution/all/b.json", "b", Some("a")),
+        ]);
+        let child = history.find("b").unwrap();
+        let parent = history.find(child.meta.parent_hash.as_deref().unwrap());
+        assert_eq!(parent.unwrap().path, "solution/all/a.json");
+        assert!(history.find("c").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::prelude::*;
+
+use crate::api::{Response, Submit};
+use crate::meta;
+use crate::problem::*;
+use crate::solution::*;
+
+// A stand-in for the contest server, backed by problem/ and the exact scorer.
+//
+// - GET /problem?problem_id={id}: {"Success": "{problem JSON}"}
+// - POST /submission: the submission id as a JSON string
+// - GET /submissions?offset={n}&limit={n}: {"Success": [submission]}, newest
+//   first
+// - GET /userboard: {"Success": {"problems": [best score or null]}}
+//
+// All but /problem need the bearer token if the judge has one. Submissions
+// live in memory.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Submission {
+    pub _id: String,
+    pub problem_id: ProblemId,
+    pub submitted_at: String,
+    pub score: Response<Score>,
+}
+
+type HttpResponse = tiny_http::Response<std::io::Cursor<Vec<u8>>>;
+
+fn json_response(code: u16, body: &impl Serialize) -> HttpResponse {
+    tiny_http::Response::from_string(serde_json::to_string(body).unwrap())
+        .with_status_code(code)
+        .with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+        )
+}
+
+fn failure(code: u16, message: impl std::fmt::Display) -> HttpResponse {
+    json_response(code, &Response::<()>::Failure(message.to_string()))
+}
+
+fn query(url: &str) -> HashMap<&str, &str> {
+    url.split_once('?')
+        .map(|(_, query)| {
+            query
+                .split('&')
+                .filter_map(|kv| kv.split_once('='))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn score(id: ProblemId, contents: &str) -> Result<Score> {
+    let problem = Problem::new(id).with_context(|| format!("unknown problem: {id}"))?;
+    let solution: Solution = serde_json::from_str(contents).context("invalid solution JSON")?;
+    solution.validate(&problem)?;
+    Ok(crate::solver_sa::exact_score(
+        &problem,
+        id,
+        id.into(),
+        &solution,
+    ))
+}
+
+pub struct Judge {
+    server: tiny_http::Server,
+    token: Option<String>,
+    submissions: Vec<Submission>,
+}
+
+impl Judge {
+    pub fn new(addr: &str, token: Option<String>) -> Result<Self> {
+        let server = tiny_http::Server::http(addr).map_err(|e| anyhow::anyhow!("{e}"))?;
+        Ok(Judge {
+            server,
+            token,
+            submissions: vec![],
+        })
+    }
+
+    pub fn addr(&self) -> String {
+        self.server.server_addr().to_string()
+    }
+
+    pub fn serve(mut self) -> Result<()> {
+        loop {
+            let mut request = self.server.recv()?;
+            let response = self.handle(&mut request);
+            info!(
+                "{} {} -> {}",
+                request.method(),
+                request.url(),
+                response.status_code().0
+            );
+            if let Err(e) = request.respond(response) {
+                warn!("respond: {e}");
+            }
+        }
+    }
+
+    fn authorized(&self, request: &tiny_http::Request) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        request.headers().iter().any(|h| {
+            h.field.equiv("Authorization") && h.value.as_str() == format!("Bearer {token}")
+        })
+    }
+
+    fn handle(&mut self, request: &mut tiny_http::Request) -> HttpResponse {
+        use tiny_http::Method;
+
+        let url = request.url().to_string();
+        let path = url.split('?').next().unwrap_or_default();
+        let query = query(&url);
+
+        if path != "/problem" && !self.authorized(request) {
+            return failure(401, "invalid token");
+        }
+
+        match (request.method(), path) {
+            (Method::Get, "/problem") => {
+                let Some(id) = query.get("problem_id").and_then(|id| id.parse().ok()) else {
+                    return failure(400, "missing problem_id");
+                };
+                let id: ProblemId = id;
+                match read_from(format!("problem/{id}.json")) {
+                    Ok(problem) => json_response(200, &Response::Success(problem)),
+                    Err(_) => failure(404, format!("unknown problem: {id}")),
+                }
+            }
+            (Method::Post, "/submission") => {
+                let mut body = String::new();
+                if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                    return failure(400, e);
+                }
+                let submit: Submit = match serde_json::from_str(&body) {
+                    Ok(submit) => submit,
+                    Err(e) => return failure(400, e),
+                };
+                let score = match score(submit.problem_id, &submit.contents) {
+                    Ok(score) => Response::Success(score),
+                    Err(e) => Response::Failure(format!("{e:#}")),
+                };
+                let id = format!("{:024x}", self.submissions.len() + 1);
+                self.submissions.push(Submission {
+                    _id: id.clone(),
+                    problem_id: submit.problem_id,
+                    submitted_at: meta::format_timestamp(meta::timestamp()),
+                    score,
+                });
+                json_response(201, &id)
+            }
+            (Method::Get, "/submissions") => {
+                let get = |key: &str, default: usize| {
+                    query
+                        .get(key)
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(default)
+                };
+                let submissions = self
+                    .submissions
+                    .iter()
+                    .rev()
+                    .skip(get("offset", 0))
+                    .take(get("limit", 10))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                json_response(200, &Response::Success(submissions))
+            }
+            (Method::Get, "/userboard") => {
+                let max_id = problem_ids().last().cloned().unwrap_or(0);
+                let mut problems: Vec<Option<Score>> = vec![None; max_id as usize];
+                for s in &self.submissions {
+                    // Submissions of unknown problems, e.g. 0, failed.
+                    let index = (s.problem_id as usize).checked_sub(1);
+                    if let (Response::Success(score), Some(best)) =
+                        (&s.score, index.and_then(|i| problems.get_mut(i)))
+                    {
+                        *best = Some(best.map_or(*score, |best| best.max(*score)));
+                    }
+                }
+                json_response(
+                    200,
+                    &Response::Success(serde_json::json!({ "problems": problems })),
+                )
+            }
+            _ => failure(404, format!("not found: {} {path}", request.method())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Client;
+
+    #[test]
+    fn judge_end_to_end() -> Result<()> {
+        let judge = Judge::new("127.0.0.1:0", Some("token".to_string()))?;
+        let url = format!("http://{}", judge.addr());
+        std::thread::spawn(move || judge.serve());
+
+        let client = Client::new(&url, "token");
+        let id = 42;
+        let solution = Solution::best(id)?;
+        client.submit(id, &solution)?;
+        let mut invalid = solution.clone();
+        invalid.placements[1] = invalid.placements[0];
+        client.submit(id, &invalid)?;
+
+        let problem = Problem::new(id)?;
+        let userboard = client.userboard()?;
+        assert_eq!(
+            userboard.best_score(id),
+            Some(crate::solver_sa::exact_score(
+                &problem,
+                id,
+                id.into(),
+                &solution
+            ))
+        );
+        assert_eq!(userboard.best_score(1), None);
+        assert_eq!(client.submissions(0, 10)?.as_array().unwrap().len(), 2);
+
+        // An unknown problem fails without breaking the userboard.
+        client.submit(0, &solution)?;
+        assert_eq!(client.userboard()?.best_score(id), userboard.best_score(id));
+
+        assert!(Client::new(&url, "wrong").userboard().is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,23 @@
+pub mod api;
+pub mod bench;
+pub mod blocker;
+pub mod compare;
 pub mod draw;
+pub mod experiment;
+pub mod history;
+pub mod judge;
+pub mod meta;
+pub mod movelog;
 pub mod prelude;
 pub mod problem;
+pub mod progress;
+pub mod report;
 pub mod solution;
 pub mod solver;
+pub mod solver_exact;
+pub mod solver_lns;
 pub mod solver_sa;
 pub mod stats;
+pub mod trace;
+pub mod transfer;
+pub mod tune;
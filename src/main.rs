@@ -1,12 +1,44 @@
 use clap::Parser;
 
+use icfp2023::api;
+use icfp2023::bench;
+use icfp2023::blocker;
+use icfp2023::compare;
 use icfp2023::draw;
+use icfp2023::experiment;
+use icfp2023::history;
+use icfp2023::judge;
+use icfp2023::movelog;
 use icfp2023::prelude::*;
 use icfp2023::problem::*;
+use icfp2023::progress;
+use icfp2023::report;
 use icfp2023::solution;
-use icfp2023::solver;
+use icfp2023::solver::{self, Solver as _};
+use icfp2023::solver_exact;
+use icfp2023::solver_lns;
 use icfp2023::solver_sa;
 use icfp2023::stats;
+use icfp2023::trace;
+use icfp2023::transfer;
+use icfp2023::tune;
+
+#[derive(clap::Args, Debug)]
+struct Api {
+    /// Defaults to $ICFP2023_API_URL, or the contest's.
+    #[arg(long)]
+    base_url: Option<String>,
+    /// File of the API token. Defaults to $ICFP2023_API_TOKEN, then
+    /// $ICFP2023_API_TOKEN_FILE.
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+}
+
+impl Api {
+    fn client(self) -> Result<api::Client> {
+        api::Client::from_env(self.base_url, self.token_file)
+    }
+}
 
 #[derive(Parser, Debug)]
 #[clap(name = "icfp2023")]
@@ -20,19 +52,275 @@ enum Cli {
         solution_path: PathBuf,
         out_path: PathBuf,
     },
+    /// Draws the score and the temperature of an SA trace,
+    /// stats/sa/{name}/{run id}/{id}.jsonl.
+    PlotTrace {
+        trace_path: PathBuf,
+        out_path: PathBuf,
+        /// Write the columns of stats/sa.gnuplot instead.
+        #[arg(long)]
+        data: bool,
+    },
+    /// Re-apply a move log of solve --move-log to its initial solution,
+    /// checking the score at each step.
+    Replay {
+        log_path: PathBuf,
+        /// Stop after this iteration.
+        #[arg(long)]
+        until: Option<usize>,
+        /// Draw the state where it stopped.
+        #[arg(long)]
+        draw: Option<PathBuf>,
+        /// Write the solution where it stopped.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// With --problem-file or --output, only writes the solution JSON, to
+    /// stdout by default, and nothing under solution/ or stats/.
     Solve {
+        /// Defaults to 0 with --problem-file, whose spec is then V2 if it has
+        /// pillars.
+        #[arg(required_unless_present = "problem_file")]
+        id: Option<ProblemId>,
+        /// Read the problem from this file instead of problem/{id}.json. '-'
+        /// for stdin.
+        #[arg(long)]
+        problem_file: Option<PathBuf>,
+        /// '-' for stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+        #[arg(long)]
+        initial_solution_path: Option<PathBuf>,
+        /// Search volumes together with placements.
+        #[arg(long)]
+        volume_search: bool,
+        /// Start from instruments packed in clusters.
+        #[arg(long, conflicts_with = "initial_solution_path")]
+        cluster_init: bool,
+        /// Use the parameters in stats/tuned.json for the class of the problem.
+        #[arg(long)]
+        tuned: bool,
+        /// Log the accepted moves to this file, for replay.
+        #[arg(long)]
+        move_log: Option<PathBuf>,
+        /// e.g. duration-3600, iter-1000000, stall-iter-100000, stall-duration-600,
+        /// target-1e9, deadline-{unix time}. Join with '+' to stop at whichever
+        /// comes first.
+        #[arg(long, default_value = "duration-60")]
+        end: solver_sa::End,
+    },
+    /// Solve problems in parallel, showing one progress line per worker.
+    SolveAll {
+        /// Defaults to all problems.
+        ids: Vec<ProblemId>,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        jobs: usize,
+        /// Warm-start from solution/best/{id}.json.
+        #[arg(long)]
+        from_best: bool,
+        /// Use the parameters in stats/tuned.json for the class of each
+        /// problem.
+        #[arg(long)]
+        tuned: bool,
+        #[arg(long, default_value = "duration-60")]
+        end: solver_sa::End,
+    },
+    Lns {
         id: ProblemId,
         #[arg(long)]
         initial_solution_path: Option<PathBuf>,
+        /// Warm-start from solution/best/{id}.json.
+        #[arg(long)]
+        from_best: bool,
+        /// # of musicians to remove and reinsert at once.
+        #[arg(long, default_value_t = 8)]
+        k: usize,
+        #[arg(long, default_value = "duration-60")]
+        end: solver_sa::End,
+    },
+    /// Branch and bound for problems with a few musicians.
+    Exact {
+        id: ProblemId,
+        /// Spacing of candidate points.
+        #[arg(long, default_value_t = 5.0)]
+        grid: Coord,
+        #[arg(long, default_value_t = 10_000_000)]
+        max_nodes: usize,
+    },
+    /// Post-process a solution by placing silent blockers.
+    OptimizeBlockers {
+        id: ProblemId,
+        /// Defaults to solution/best/{id}.json.
+        solution_path: Option<PathBuf>,
+        #[arg(long, default_value_t = 10)]
+        rounds: usize,
+    },
+    /// Map a solution of one problem onto another.
+    Transfer {
+        source_id: ProblemId,
+        target_id: ProblemId,
+        /// Defaults to solution/best/{source_id}.json.
+        #[arg(long)]
+        source_solution_path: Option<PathBuf>,
+        /// Run SA from the transferred solution.
+        #[arg(long)]
+        solve: bool,
+        #[arg(long, default_value = "duration-60")]
+        end: solver_sa::End,
     },
     Bench {
         id: ProblemId,
     },
+    /// Run every combination of problems, seeds and solver parameters of an
+    /// experiment, e.g. experiment/temp0.json.
+    Experiment {
+        spec_path: PathBuf,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        jobs: usize,
+        /// Promote results to solution/best.
+        #[arg(long)]
+        save_best: bool,
+    },
+    /// Race SA parameters per problem class and save the winners in
+    /// stats/tuned.json, e.g. experiment/tune.json.
+    Tune {
+        race_path: PathBuf,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        jobs: usize,
+    },
+    /// Measure the problems of the benchmark suite and compare them with the
+    /// baseline.
+    BenchSuite {
+        /// Defaults to the suite.
+        ids: Vec<ProblemId>,
+        /// Defaults to stats/bench.json.
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Relative slowdown which counts as a regression.
+        #[arg(long, default_value = "0.1")]
+        tolerance: f64,
+        /// Merge the result into the baseline, unless there are regressions.
+        #[arg(long)]
+        save: bool,
+        /// Save even with regressions.
+        #[arg(long, requires = "save")]
+        force: bool,
+    },
     Score {
         id: ProblemId,
         solution_path: PathBuf,
     },
-    BestScoreRefresh,
+    /// Rescore solution/best and print what changed.
+    BestScoreRefresh {
+        #[arg(long, default_value = "fast")]
+        scorer: solver_sa::Scorer,
+        /// Print the diff without writing stats/best-score.json.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check that stats/best-score.json agrees with solution/best.
+    CheckBest,
+    UpperBound,
+    Submit {
+        id: ProblemId,
+        solution_path: PathBuf,
+        #[command(flatten)]
+        api: Api,
+    },
+    /// Submit best solutions which differ from the last submitted ones, and
+    /// refresh stats/userboard.json.
+    SubmitBest {
+        /// Defaults to all problems.
+        ids: Vec<ProblemId>,
+        #[command(flatten)]
+        api: Api,
+    },
+    Submissions {
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        #[command(flatten)]
+        api: Api,
+    },
+    /// Fetch the userboard into stats/userboard.json.
+    Userboard {
+        #[command(flatten)]
+        api: Api,
+    },
+    /// Compare stats/userboard.json with the local best scores and list what
+    /// submit-best would submit.
+    CompareUserboard,
+    /// Score the solutions of two directories, e.g. solution/best and
+    /// solution/submission, and compare them per problem.
+    Compare {
+        a: PathBuf,
+        b: PathBuf,
+        #[arg(long, default_value = "fast")]
+        scorer: solver_sa::Scorer,
+        /// Also write the comparison as CSV.
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+    /// Serve the contest API locally, scoring with the exact scorer.
+    ServeJudge {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Bearer token which clients must send. Defaults to
+        /// $ICFP2023_API_TOKEN. Any token is accepted if neither is given.
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Write an HTML and a Markdown report of the best solutions.
+    Report {
+        #[arg(long, default_value = "report")]
+        out_dir: PathBuf,
+    },
+    /// List saved runs for a problem, oldest first.
+    History {
+        id: ProblemId,
+    },
+    /// Delete saved runs which scored no higher than another run of the same
+    /// problem in no less time.
+    Prune {
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+// '-' is stdin.
+fn read_input(path: &Path) -> Result<String> {
+    use std::io::Read as _;
+
+    if path == Path::new("-") {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)?;
+        Ok(s)
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("read {path:?}"))
+    }
+}
+
+// '-' is stdout.
+fn write_output(path: &Path, content: &str) -> Result<()> {
+    if path == Path::new("-") {
+        println!("{content}");
+        Ok(())
+    } else {
+        std::fs::write(path, content).with_context(|| format!("write {path:?}"))
+    }
 }
 
 fn main() -> Result<()> {
@@ -48,37 +336,336 @@ fn main() -> Result<()> {
         } => {
             draw::draw_solution_file(id, solution_path, out_path)?;
         }
+        Cli::PlotTrace {
+            trace_path,
+            out_path,
+            data,
+        } => {
+            let (header, records) = trace::read(trace_path)?;
+            if data {
+                trace::write_data(&header, &records, out_path)?;
+            } else {
+                trace::plot(&header, &records, out_path)?;
+            }
+        }
+        Cli::Replay {
+            log_path,
+            until,
+            draw,
+            output,
+        } => {
+            let (header, steps) = movelog::read(&log_path)?;
+            let replayed = movelog::replay(&header, &steps, until)?;
+            println!(
+                "Replayed {} of {} steps up to iteration {}, score: {}",
+                replayed.nsteps,
+                steps.len(),
+                replayed.iteration,
+                replayed.score
+            );
+            if let Some(path) = draw {
+                draw::draw_svg(&header.problem, Some(&replayed.solution), path)?;
+            }
+            if let Some(path) = output {
+                write_output(&path, &serde_json::to_string(&replayed.solution)?)?;
+            }
+        }
         Cli::Solve {
             id,
+            problem_file,
+            output,
             initial_solution_path,
+            volume_search,
+            cluster_init,
+            tuned,
+            move_log,
+            end,
         } => {
-            solver::solve(solver_sa::SolverSa::new(
-                id,
+            solver::handle_interrupt()?;
+            let problem = match &problem_file {
+                Some(path) => Problem::from_json(&read_input(path)?)?,
+                None => Problem::new(id.unwrap())?,
+            };
+            let spec = id.map_or(problem.guess_spec(), Spec::from);
+            let params = if tuned {
+                let class = Class::new(spec, &problem);
+                Some(
+                    tune::tuned(class)?
+                        .with_context(|| format!("no tuned parameters for {class}"))?,
+                )
+            } else {
+                None
+            };
+            let initial_solution = if cluster_init {
+                Some(solver_sa::SolverSa::cluster_initial_solution(&problem))
+            } else {
+                initial_solution_path.and_then(|path| solution::Solution::from(path).ok())
+            };
+            let mut solver = solver_sa::SolverSa::with_problem(
+                id.unwrap_or(0),
+                spec,
+                problem,
                 // 5_000_000.0,
                 Some(100.0),
-                // 3 hours.
-                // solver_sa::End::MaxDuration(std::time::Duration::from_secs(12 * 3_600)),
-                solver_sa::End::MaxDuration(std::time::Duration::from_secs(60)),
-                // solver_sa::End::MaxDuration(std::time::Duration::from_secs(3600)),
-                initial_solution_path.and_then(|path| solution::Solution::from(path).ok()),
+                end,
+                volume_search,
+                initial_solution,
+            );
+            if let Some(params) = params {
+                solver.set_params(params)?;
+            }
+            if let Some(path) = move_log {
+                solver.set_move_log(path);
+            }
+            if problem_file.is_none() && output.is_none() {
+                solver::solve(solver)?;
+            } else {
+                solver.hide_trace();
+                let solved = solver.solve()?;
+                info!("score: {}", solved.score);
+                let json = serde_json::to_string(&solved.solution())?;
+                write_output(&output.unwrap_or_else(|| PathBuf::from("-")), &json)?;
+            }
+        }
+        Cli::SolveAll {
+            ids,
+            jobs,
+            from_best,
+            tuned,
+            end,
+        } => {
+            use rayon::prelude::*;
+            solver::handle_interrupt()?;
+            let ids = if ids.is_empty() { problem_ids() } else { ids };
+            let workers = progress::Workers::new(jobs);
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()?
+                .install(|| {
+                    ids.par_iter().for_each(|&id| {
+                        // Don't start the queued ones.
+                        if solver::interrupted() {
+                            return;
+                        }
+                        let result = (|| {
+                            let initial_solution = if from_best {
+                                solution::Solution::best(id).ok()
+                            } else {
+                                None
+                            };
+                            let mut solver = solver_sa::SolverSa::new(
+                                id,
+                                Some(100.0),
+                                end.clone(),
+                                false,
+                                initial_solution,
+                            )?;
+                            if tuned {
+                                let class = Class::new(id.into(), &Problem::new(id)?);
+                                match tune::tuned(class)? {
+                                    Some(params) => solver.set_params(params)?,
+                                    None => warn!(
+                                        "problem_id: {id}: no tuned parameters for {class}, using the defaults"
+                                    ),
+                                }
+                            }
+                            solver::solve_with_progress(solver, workers.bar())
+                        })();
+                        if let Err(e) = result {
+                            error!("problem_id: {id}: {e:?}");
+                        }
+                    })
+                });
+        }
+        Cli::Lns {
+            id,
+            initial_solution_path,
+            from_best,
+            k,
+            end,
+        } => {
+            solver::handle_interrupt()?;
+            let initial_solution = if from_best {
+                Some(solution::Solution::best(id)?)
+            } else {
+                initial_solution_path.and_then(|path| solution::Solution::from(path).ok())
+            };
+            solver::solve(solver_lns::SolverLns::new(
+                id,
+                k,
+                None,
+                end,
+                initial_solution,
             )?)?;
         }
+        Cli::Exact {
+            id,
+            grid,
+            max_nodes,
+        } => {
+            solver::solve(solver_exact::SolverExact::new(id, grid, max_nodes)?)?;
+        }
+        Cli::OptimizeBlockers {
+            id,
+            solution_path,
+            rounds,
+        } => {
+            let solution = match solution_path {
+                Some(path) => solution::Solution::from(path)?,
+                None => solution::Solution::best(id)?,
+            };
+            solver::solve(blocker::BlockerOptimizer::new(id, rounds, solution)?)?;
+        }
+        Cli::Transfer {
+            source_id,
+            target_id,
+            source_solution_path,
+            solve,
+            end,
+        } => {
+            let source_solution = match source_solution_path {
+                Some(path) => solution::Solution::from(path)?,
+                None => solution::Solution::best(source_id)?,
+            };
+            let target = Problem::new(target_id)?;
+            let solution = transfer::transfer(&Problem::new(source_id)?, &source_solution, &target);
+            let score = solver_sa::score(&target, target_id, target_id.into(), &solution);
+            let path = format!("solution/transfer/{target_id}-from-{source_id}.json");
+            write_to(&path, &serde_json::to_string(&solution)?)?;
+            println!("{path}: {score}");
+            if solve {
+                solver::handle_interrupt()?;
+                solver::solve(solver_sa::SolverSa::new(
+                    target_id,
+                    Some(100.0),
+                    end,
+                    false,
+                    Some(solution),
+                )?)?;
+            }
+        }
         Cli::Bench { id } => {
             solver::solve(solver_sa::SolverSa::new(
                 id,
                 Some(100.0),
                 solver_sa::End::MaxIteration(50_000),
+                false,
                 None,
             )?)?;
         }
+        Cli::Experiment {
+            spec_path,
+            jobs,
+            save_best,
+        } => {
+            let spec = serde_json::from_str(&read_input(&spec_path)?)
+                .with_context(|| format!("parse {spec_path:?}"))?;
+            let dir = experiment::run(&spec, jobs, save_best)?;
+            println!("Wrote {}", dir.display());
+        }
+        Cli::Tune { race_path, jobs } => {
+            let race = serde_json::from_str(&read_input(&race_path)?)
+                .with_context(|| format!("parse {race_path:?}"))?;
+            tune::tune(&race, jobs)?;
+            println!("Wrote {}", tune::TUNED);
+        }
+        Cli::BenchSuite {
+            ids,
+            baseline,
+            tolerance,
+            save,
+            force,
+        } => {
+            let ids = if ids.is_empty() {
+                bench::SUITE.to_vec()
+            } else {
+                ids
+            };
+            let baseline = baseline.unwrap_or_else(|| project_path(bench::BASELINE));
+            let regressions = bench::run(&ids, &baseline, tolerance, save, force)?;
+            ensure!(
+                regressions.is_empty(),
+                "{} regressions against {}",
+                regressions.len(),
+                baseline.display()
+            );
+        }
         Cli::Score { id, solution_path } => {
             let problem = Problem::new(id)?;
             let solution = solution::Solution::from(solution_path)?;
             let score = solver_sa::score(&problem, id, id.into(), &solution);
             println!("{score}");
         }
-        Cli::BestScoreRefresh => {
-            stats::BestScore::refresh()?;
+        Cli::BestScoreRefresh { scorer, dry_run } => {
+            stats::BestScore::refresh(scorer, dry_run)?;
+        }
+        Cli::CheckBest => {
+            let issues = stats::BestScore::check()?;
+            for (id, issue) in &issues {
+                println!("{id}: {issue}");
+            }
+            ensure!(issues.is_empty(), "{} inconsistent problems", issues.len());
+            println!("OK");
+        }
+        Cli::UpperBound => {
+            stats::UpperBound::stats()?;
+        }
+        Cli::Submit {
+            id,
+            solution_path,
+            api,
+        } => {
+            let solution = solution::Solution::from(solution_path)?;
+            solution.validate(&Problem::new(id)?)?;
+            println!("submission_id: {}", api.client()?.submit(id, &solution)?);
+        }
+        Cli::SubmitBest { ids, api } => {
+            let ids = if ids.is_empty() { problem_ids() } else { ids };
+            let client = api.client()?;
+            let nsubmit = client.submit_best(&ids)?;
+            println!("Submitted {nsubmit} solutions");
+            client.refresh_userboard()?.stats();
+        }
+        Cli::Submissions { offset, limit, api } => {
+            let submissions = api.client()?.submissions(offset, limit)?;
+            println!("{}", serde_json::to_string_pretty(&submissions)?);
+        }
+        Cli::Userboard { api } => {
+            api.client()?.refresh_userboard()?.stats();
+        }
+        Cli::CompareUserboard => {
+            stats::Comparison::stats()?;
+        }
+        Cli::Compare { a, b, scorer, csv } => {
+            let versus = compare::compare(&a, &b, scorer);
+            compare::print(&a, &b, &versus);
+            if let Some(csv) = csv {
+                compare::write_csv(&csv, &versus)?;
+            }
+        }
+        Cli::ServeJudge { addr, token } => {
+            let token = token.or_else(|| std::env::var("ICFP2023_API_TOKEN").ok());
+            let judge = judge::Judge::new(&addr, token)?;
+            println!("Serving on http://{}", judge.addr());
+            judge.serve()?;
+        }
+        Cli::Report { out_dir } => {
+            let path = report::write_report(&out_dir)?;
+            println!("Wrote {}", path.display());
+        }
+        Cli::History { id } => {
+            history::History::new()?.print(id);
+        }
+        Cli::Prune { dry_run } => {
+            let removed = history::History::prune(dry_run)?;
+            for e in &removed {
+                println!("{}", e.path);
+            }
+            println!(
+                "{} {} runs",
+                if dry_run { "Would remove" } else { "Removed" },
+                removed.len()
+            );
         }
     }
     Ok(())
@@ -0,0 +1,133 @@
+use crate::prelude::*;
+
+use crate::problem::ProblemId;
+use crate::solution::Solution;
+
+/// Provenance of a saved solution. Saved as `{name}.meta.json` next to
+/// `{name}.json`, which stays in the judge's format.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Meta {
+    pub problem_id: ProblemId,
+    pub problem_hash: String,
+    pub solver_name: String,
+    pub params: serde_json::Value,
+    pub seed: Option<u64>,
+    pub niter: Option<usize>,
+    pub runtime_secs: f64,
+    /// `solver_sa::score`.
+    pub score: Score,
+    /// `solver_sa::exact_score`, which the judge would report.
+    pub exact_score: Score,
+    pub solution_hash: String,
+    /// Hash of the solution which the solver started from. `History::find`
+    /// maps it to the run which saved that solution.
+    pub parent_hash: Option<String>,
+    pub git_revision: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Meta {
+    pub fn path(solution_path: &str) -> String {
+        match solution_path.strip_suffix(".json") {
+            Some(stem) => format!("{stem}.meta.json"),
+            None => format!("{solution_path}.meta.json"),
+        }
+    }
+
+    pub fn read(solution_path: &str) -> Result<Meta> {
+        let s = read_from(Meta::path(solution_path))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+}
+
+// FNV-1a. Stable across builds, unlike `DefaultHasher`.
+pub fn content_hash(content: &[u8]) -> String {
+    let hash = content.iter().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+pub fn problem_hash(id: ProblemId) -> Result<String> {
+    Ok(content_hash(
+        read_from(format!("problem/{id}.json"))?.as_bytes(),
+    ))
+}
+
+/// The hash of `solution` as it is saved.
+pub fn solution_hash(solution: &Solution) -> String {
+    content_hash(serde_json::to_string(solution).unwrap().as_bytes())
+}
+
+pub fn git_revision() -> Option<String> {
+    static REVISION: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    REVISION
+        .get_or_init(|| {
+            let output = std::process::Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .current_dir(project_path(""))
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .clone()
+}
+
+/// Time of the last commit which touched `relative_path`.
+pub fn git_commit_time(relative_path: &str) -> Option<u64> {
+    let output = std::process::Command::new("git")
+        .args(["log", "-1", "--format=%ct", "--", relative_path])
+        .current_dir(project_path(""))
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+pub fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` of seconds since the Unix epoch.
+pub fn format_timestamp(secs: u64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hash_test() {
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        assert_eq!(
+            Meta::path("solution/best/1.json"),
+            "solution/best/1.meta.json"
+        );
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_688_750_400), "2023-07-07T17:20:00Z");
+    }
+}
@@ -0,0 +1,262 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+use crate::solver_sa::LocalState;
+use crate::trace::MoveKind;
+
+// Logs of the moves which SA accepted, to replay the path it took. JSONL: a
+// `Header` with the initial solution, then one `Step` per accepted move.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Header {
+    /// 0 for a problem which isn't under problem/, which `problem` covers.
+    pub problem_id: ProblemId,
+    pub problem: Problem,
+    pub spec: Spec,
+    pub volume_search: bool,
+    /// `REBUILD_INTERVAL` of the run.
+    pub rebuild_interval: usize,
+    /// Of the initial solution.
+    pub score: Score,
+    pub solution: Solution,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Move {
+        musician: usize,
+        from: Point,
+        to: Point,
+    },
+    /// `from`: positions of `a` and `b` before the swap.
+    Swap {
+        a: usize,
+        b: usize,
+        from: (Point, Point),
+    },
+    Volume {
+        musician: usize,
+        from: Volume,
+        to: Volume,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Step {
+    pub iteration: usize,
+    pub kind: MoveKind,
+    /// In order. A cluster move changes several musicians.
+    pub changes: Vec<Change>,
+    pub delta: Score,
+    /// After the move.
+    pub score: Score,
+}
+
+pub struct MoveLog {
+    file: std::io::BufWriter<std::fs::File>,
+}
+
+impl MoveLog {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        info!("move log: {}", path.display());
+        Ok(MoveLog {
+            file: std::io::BufWriter::new(
+                std::fs::File::create(path).with_context(|| format!("create {path:?}"))?,
+            ),
+        })
+    }
+
+    pub fn header(&mut self, header: &Header) -> Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(header)?)?;
+        Ok(())
+    }
+
+    pub fn step(
+        &mut self,
+        iteration: usize,
+        kind: MoveKind,
+        before: Score,
+        after: Score,
+        changes: Vec<Change>,
+    ) -> Result<()> {
+        let step = Step {
+            iteration,
+            kind,
+            changes,
+            delta: after - before,
+            score: after,
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&step)?)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read(path: impl AsRef<Path>) -> Result<(Header, Vec<Step>)> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("read {path:?}"))?;
+    let mut lines = s.lines().filter(|line| !line.trim().is_empty());
+    let header = serde_json::from_str(lines.next().context("empty move log")?)?;
+    let steps = lines
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect::<Result<_>>()?;
+    Ok((header, steps))
+}
+
+// Scores differ slightly from the log because SA also applied and undid the
+// moves which it rejected.
+const TOLERANCE: f64 = 1e-6;
+
+// Relative to `scale`, the score, since a delta can be tiny.
+fn check(what: &str, replayed: Score, logged: Score, scale: Score) -> Result<()> {
+    ensure!(
+        (replayed - logged).abs() <= TOLERANCE * scale.abs().max(1.0),
+        "{what}: replayed {replayed} != logged {logged}"
+    );
+    Ok(())
+}
+
+fn check_place(st: &LocalState, i: usize, p: Point) -> Result<()> {
+    ensure!(
+        st.place[i].distance_squared(p) < EPS,
+        "musician {i} is at {}, not {p}",
+        st.place[i]
+    );
+    Ok(())
+}
+
+fn apply(st: &mut LocalState, change: &Change) -> Result<()> {
+    match *change {
+        Change::Move { musician, from, to } => {
+            check_place(st, musician, from)?;
+            st.do_move(musician, to);
+        }
+        Change::Swap { a, b, from } => {
+            check_place(st, a, from.0)?;
+            check_place(st, b, from.1)?;
+            st.do_swap(a, b);
+        }
+        Change::Volume { musician, from, to } => {
+            ensure!(
+                st.volume(musician) == Some(from),
+                "musician {musician} has volume {:?}, not {from}",
+                st.volume(musician)
+            );
+            st.do_volume(musician, to);
+        }
+    }
+    Ok(())
+}
+
+pub struct Replayed {
+    /// Of the last replayed step.
+    pub iteration: usize,
+    pub nsteps: usize,
+    pub score: Score,
+    pub solution: Solution,
+}
+
+/// Re-applies `steps` to the initial solution up to `until`, checking the
+/// score after each step.
+pub fn replay(header: &Header, steps: &[Step], until: Option<usize>) -> Result<Replayed> {
+    let new_state = |solution: &Solution| {
+        let mut st = LocalState::new(&header.problem, header.problem_id, header.spec, solution);
+        if header.volume_search {
+            st.set_volumes(solution.volumes.clone());
+        }
+        st
+    };
+
+    let mut st = new_state(&header.solution);
+    check("initial score", st.score, header.score, header.score)?;
+    let until = until.unwrap_or(usize::MAX);
+    let interval = header.rebuild_interval;
+    let mut iteration = 0;
+    let mut nsteps = 0;
+    for step in steps.iter().take_while(|step| step.iteration <= until) {
+        // As `run_sa` does, to drop accumulated errors.
+        if step.iteration / interval > iteration / interval {
+            st = new_state(&st.to_solution());
+        }
+        let before = st.score;
+        for change in &step.changes {
+            apply(&mut st, change)
+                .with_context(|| format!("iteration {}: {change:?}", step.iteration))?;
+        }
+        let what = format!("iteration {}, {}", step.iteration, step.kind.name());
+        check(&format!("{what}: score"), st.score, step.score, step.score)?;
+        check(
+            &format!("{what}: delta"),
+            st.score - before,
+            step.delta,
+            step.score,
+        )?;
+        iteration = step.iteration;
+        nsteps += 1;
+    }
+    Ok(Replayed {
+        iteration,
+        nsteps,
+        score: st.score,
+        solution: st.to_solution(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+    use crate::solver::seeded_rng;
+    use crate::solver_sa::{run_sa, End, Params, SolverSa, REBUILD_INTERVAL};
+    use crate::trace::Trace;
+
+    #[test]
+    fn replay_run() -> Result<()> {
+        // As with --problem-file, which `Header::problem` covers.
+        let id = 0;
+        let problem = Problem::new(60)?;
+        let initial = SolverSa::initial_solution(&problem);
+        let path = std::env::temp_dir().join(format!("movelog-{}.jsonl", std::process::id()));
+        let mut trace = Trace::hidden();
+        trace.set_move_log(MoveLog::create(&path)?);
+        let (best, _, _) = run_sa(
+            &mut trace,
+            &mut seeded_rng(0),
+            &problem,
+            id,
+            Spec::V2,
+            &initial,
+            &Params::default(),
+            &End::MaxIteration(5_000),
+            true,
+            &Progress::hidden(),
+        )?;
+        drop(trace);
+
+        let (header, mut steps) = read(&path)?;
+        std::fs::remove_file(&path)?;
+        assert!(steps.last().unwrap().iteration > 2 * REBUILD_INTERVAL);
+        let replayed = replay(&header, &steps, None)?;
+        assert_eq!(replayed.nsteps, steps.len());
+        assert!(replayed.score <= best + TOLERANCE * best.abs());
+
+        let until = steps[steps.len() / 2].iteration;
+        let replayed = replay(&header, &steps, Some(until))?;
+        assert_eq!(replayed.iteration, until);
+
+        // A log with a missing step doesn't replay.
+        steps.remove(0);
+        assert!(replay(&header, &steps, None).is_err());
+        Ok(())
+    }
+}
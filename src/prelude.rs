@@ -6,7 +6,7 @@ pub use rand::rngs::StdRng;
 pub use rand::Rng;
 pub use rand::SeedableRng;
 pub use serde::{Deserialize, Serialize};
-pub use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+pub use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 pub use std::io::Write;
 pub use std::ops::Deref;
 pub use std::ops::Index;
@@ -33,6 +33,22 @@ pub fn write_to(relative_path: impl AsRef<Path>, content: &str) -> Result<()> {
     Ok(std::fs::write(path, content)?)
 }
 
+/// Writes to a temporary file and renames it, so that readers see either the
+/// old content or the new one.
+pub fn write_atomic(relative_path: impl AsRef<Path>, content: &str) -> Result<()> {
+    static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let path = project_path(relative_path);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(format!(
+        ".tmp.{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    ));
+    std::fs::write(&tmp, content)?;
+    Ok(std::fs::rename(tmp, path)?)
+}
+
 pub type Score = f64;
 pub type Coord = f64;
 pub type Instrument = usize;
@@ -41,6 +57,8 @@ pub type Volume = Score;
 pub const MUSICIAN_RADIUS: Coord = 10.0;
 pub const MUSICIAN_RADIUS_2: Coord = MUSICIAN_RADIUS * MUSICIAN_RADIUS;
 
+pub const MAX_VOLUME: Volume = 10.0;
+
 pub const BLOCK_RADIUS: Coord = 5.0;
 pub const BLOCK_RADIUS_2: Coord = BLOCK_RADIUS * BLOCK_RADIUS;
 
@@ -85,8 +103,7 @@ pub fn point_to_segment_distance_squared(p: Point, (p1, p2): (Point, Point)) ->
     let a = a.clamp(0.0, 1.0);
     let closest_x = p1.x + a * dx;
     let closest_y = p1.y + a * dy;
-    let distance_squared = (closest_x - p.x).powi(2) + (closest_y - p.y).powi(2);
-    distance_squared
+    (closest_x - p.x).powi(2) + (closest_y - p.y).powi(2)
 }
 
 pub fn inst_cnt(musicians: &[Instrument]) -> HashMap<Instrument, usize> {
@@ -4,12 +4,38 @@ pub type ProblemId = u64;
 
 pub const MAX_PROMLEM_ID: ProblemId = 90;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Spec {
     V1,
     V2,
 }
 
+// Problems with at least this many musicians are large.
+const LARGE_MUSICIANS: usize = 200;
+
+/// Problems which behave alike, e.g. for tuning.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Class {
+    pub spec: Spec,
+    pub large: bool,
+}
+
+impl Class {
+    pub fn new(spec: Spec, problem: &Problem) -> Self {
+        Class {
+            spec,
+            large: problem.musicians.len() >= LARGE_MUSICIANS,
+        }
+    }
+}
+
+impl std::fmt::Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = if self.large { "large" } else { "small" };
+        write!(f, "{:?}-{size}", self.spec)
+    }
+}
+
 const V1_PROBLEM_ID_END: ProblemId = 55;
 const V2_PROBLEM_ID_START: ProblemId = V1_PROBLEM_ID_END + 1;
 
@@ -82,6 +108,20 @@ impl Problem {
         Ok(problem)
     }
 
+    pub fn from_json(s: &str) -> Result<Problem> {
+        serde_json::from_str(s).context("parse problem")
+    }
+
+    /// The spec of a problem whose id is unknown. Only V2 problems have
+    /// pillars.
+    pub fn guess_spec(&self) -> Spec {
+        if self.pillars.is_empty() {
+            Spec::V1
+        } else {
+            Spec::V2
+        }
+    }
+
     pub fn example() -> Result<Problem> {
         let s = read_from("problem/example/example-problem.json")?;
         let problem: Problem = serde_json::from_str(&s).unwrap();
@@ -166,6 +206,139 @@ impl Problem {
             .0
     }
 
+    // Musicians can stand anywhere in [minx, maxx] x [miny, maxy].
+    fn placeable_area(&self) -> (Coord, Coord, Coord, Coord) {
+        let minx = self.stage_bottom_left[0] + MUSICIAN_RADIUS;
+        let maxx = (self.stage_bottom_left[0] + self.stage_width - MUSICIAN_RADIUS).max(minx);
+        let miny = self.stage_bottom_left[1] + MUSICIAN_RADIUS;
+        let maxy = (self.stage_bottom_left[1] + self.stage_height - MUSICIAN_RADIUS).max(miny);
+        (minx, maxx, miny, maxy)
+    }
+
+    /// The impact of a musician playing `inst` at `p`, counting only attendees
+    /// who like `inst`. Blocking and volume are ignored.
+    pub fn positive_impact(&self, inst: Instrument, p: Point) -> Score {
+        self.attendees
+            .iter()
+            .filter(|a| a.tastes[inst] > 0.0)
+            .map(|a| 1e6 * a.tastes[inst] / p.distance_squared(a.point()))
+            .sum()
+    }
+
+    /// An upper bound of `positive_impact(inst, p)` over every `p` on the stage.
+    ///
+    /// Branch and bound over cells of the placeable area: a cell whose center
+    /// is `c` and half diagonal is `r` can't be closer than `d(c, a) - r` to
+    /// an attendee `a`, nor closer than the distance from `a` to the area.
+    pub fn best_position_value(&self, inst: Instrument) -> Score {
+        use std::collections::BinaryHeap;
+
+        let attendees = self
+            .attendees
+            .iter()
+            .filter(|a| a.tastes[inst] > 0.0)
+            .map(|a| (a.point(), 1e6 * a.tastes[inst]))
+            .collect::<Vec<_>>();
+        if attendees.is_empty() {
+            return 0.0;
+        }
+
+        let (minx, maxx, miny, maxy) = self.placeable_area();
+        let dmin = attendees
+            .iter()
+            .map(|(a, _)| {
+                let dx = (minx - a.x).max(a.x - maxx).max(0.0);
+                let dy = (miny - a.y).max(a.y - maxy).max(0.0);
+                dx.hypot(dy)
+            })
+            .collect::<Vec<_>>();
+
+        let cell_bound = |x: Coord, y: Coord, w: Coord, h: Coord| -> Score {
+            let c = Point::new(x + w / 2.0, y + h / 2.0);
+            let r = w.hypot(h) / 2.0;
+            attendees
+                .iter()
+                .zip(dmin.iter())
+                .map(|((a, w), dmin)| {
+                    let d = (c.distance(*a) - r).max(*dmin);
+                    w / (d * d)
+                })
+                .sum()
+        };
+
+        let mut lower = self.positive_impact(inst, Point::new(minx, miny));
+        // (bound, bottom-left, size)
+        let mut heap = BinaryHeap::new();
+        heap.push((
+            OrderedFloat(cell_bound(minx, miny, maxx - minx, maxy - miny)),
+            [OrderedFloat(minx), OrderedFloat(miny)],
+            [OrderedFloat(maxx - minx), OrderedFloat(maxy - miny)],
+        ));
+
+        const MAX_SPLIT: usize = 20_000;
+        const TOLERANCE: Score = 1e-4;
+        for _ in 0..MAX_SPLIT {
+            let (bound, [x, y], [w, h]) = heap.pop().unwrap();
+            if bound.0 <= lower * (1.0 + TOLERANCE) {
+                return bound.0;
+            }
+            let (w, h) = (w.0 / 2.0, h.0 / 2.0);
+            for (x, y) in [
+                (x.0, y.0),
+                (x.0 + w, y.0),
+                (x.0, y.0 + h),
+                (x.0 + w, y.0 + h),
+            ] {
+                lower = lower.max(self.positive_impact(inst, Point::new(x + w / 2.0, y + h / 2.0)));
+                heap.push((
+                    OrderedFloat(cell_bound(x, y, w, h)),
+                    [OrderedFloat(x), OrderedFloat(y)],
+                    [OrderedFloat(w), OrderedFloat(h)],
+                ));
+            }
+        }
+        heap.peek().unwrap().0 .0
+    }
+
+    /// An upper bound of the fast score (`solver_sa::score`) of any valid
+    /// solution. `exact_upper_bound` also covers the judge's score.
+    ///
+    /// Each musician is bounded on its own: volume 10 at the best unblocked
+    /// position for its instrument, where attendees with negative taste are
+    /// assumed to be blocked. In V2, the closeness factor is capped by
+    /// `closeness_cap`. Blocking by musicians or pillars only lowers the
+    /// score, so it is ignored.
+    pub fn upper_bound(&self, spec: Spec) -> Score {
+        inst_cnt(&self.musicians)
+            .into_iter()
+            .map(|(inst, cnt)| {
+                let q = closeness_factor_cap(spec, cnt);
+                self.best_position_value(inst) * q * (cnt as Score) * MAX_VOLUME
+            })
+            .sum()
+    }
+
+    /// An upper bound of the score of any valid solution with either scorer.
+    ///
+    /// `solver_sa::exact_score` rounds up each term, `ceil(q * v *
+    /// ceil(impact))`, which adds less than `q * v + 1` to it. Only attendees
+    /// who like the instrument have positive terms.
+    pub fn exact_upper_bound(&self, spec: Spec) -> Score {
+        let slack = inst_cnt(&self.musicians)
+            .into_iter()
+            .map(|(inst, cnt)| {
+                let q = closeness_factor_cap(spec, cnt);
+                let npositive = self
+                    .attendees
+                    .iter()
+                    .filter(|a| a.tastes[inst] > 0.0)
+                    .count();
+                (q * MAX_VOLUME + 1.0) * (cnt * npositive) as Score
+            })
+            .sum::<Score>();
+        self.upper_bound(spec) + slack
+    }
+
     pub fn tentative_score(&self) -> Score {
         let inst_cnt = inst_cnt(&self.musicians);
         inst_cnt
@@ -176,7 +349,7 @@ impl Problem {
                     .iter()
                     .map(|a| {
                         let d2 = self.distance_to_stage_squared(a.point());
-                        a.tastes[inst as usize] / d2
+                        a.tastes[inst] / d2
                     })
                     .sum::<Score>();
                 if impact > 0.0 {
@@ -193,6 +366,46 @@ impl Problem {
     }
 }
 
+/// The largest closeness factor a musician can get in an instrument group of
+/// `cnt` musicians.
+///
+/// Musicians are at least `MUSICIAN_RADIUS` apart, so disks of radius
+/// `MUSICIAN_RADIUS / 2` around them don't overlap. The `m`-th nearest
+/// neighbor is at distance `d` only if `m + 1` such disks fit in a disk of
+/// radius `d + MUSICIAN_RADIUS / 2`.
+pub fn closeness_cap(cnt: usize) -> Score {
+    let r = MUSICIAN_RADIUS / 2.0;
+    1.0 + (1..cnt)
+        .map(|m| {
+            let d = (r * ((m + 1) as Coord).sqrt() - r).max(MUSICIAN_RADIUS);
+            1.0 / d
+        })
+        .sum::<Score>()
+}
+
+fn closeness_factor_cap(spec: Spec, cnt: usize) -> Score {
+    match spec {
+        Spec::V1 => 1.0,
+        Spec::V2 => closeness_cap(cnt),
+    }
+}
+
+/// Problem ids which have `problem/{id}.json`.
+pub fn problem_ids() -> Vec<ProblemId> {
+    let Ok(entries) = std::fs::read_dir(project_path("problem")) else {
+        return vec![];
+    };
+    let mut ids = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            name.to_str()?.strip_suffix(".json")?.parse().ok()
+        })
+        .collect::<Vec<ProblemId>>();
+    ids.sort();
+    ids
+}
+
 impl std::fmt::Display for Problem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -226,4 +439,33 @@ mod tests {
         assert_eq!(problem.musicians.len(), 5);
         Ok(())
     }
+
+    #[test]
+    fn guess_spec() -> Result<()> {
+        for id in problem_ids() {
+            let problem = Problem::from_json(&read_from(format!("problem/{id}.json"))?)?;
+            assert_eq!(problem.guess_spec(), Spec::from(id), "{id}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn upper_bound_covers_best() -> Result<()> {
+        for id in [42, 56] {
+            let problem = Problem::new(id)?;
+            let best = crate::solution::Solution::best(id)?;
+            let score = crate::solver_sa::score(&problem, id, id.into(), &best);
+            assert!(score <= problem.upper_bound(id.into()));
+            let exact = crate::solver_sa::exact_score(&problem, id, id.into(), &best);
+            assert!(exact <= problem.exact_upper_bound(id.into()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn closeness_cap_test() {
+        assert_eq!(closeness_cap(1), 1.0);
+        assert_eq!(closeness_cap(2), 1.1);
+        assert!(closeness_cap(1000) < 1.0 + 999.0 / MUSICIAN_RADIUS);
+    }
 }
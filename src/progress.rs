@@ -0,0 +1,103 @@
+use crate::prelude::*;
+
+use crate::problem::ProblemId;
+use crate::solver_sa::{End, Run};
+use crate::stats::BestScore;
+
+use indicatif::{MultiProgress, ProgressStyle};
+
+// Resolution of the bar when the end condition has a budget.
+const BAR_LEN: u64 = 1_000;
+
+/// A live progress line of a solver.
+pub struct Progress {
+    bar: ProgressBar,
+    stored_best: Option<Score>,
+}
+
+impl Progress {
+    pub fn hidden() -> Self {
+        Progress {
+            bar: ProgressBar::hidden(),
+            stored_best: None,
+        }
+    }
+
+    /// Shows progress on `bar`. Gains are relative to the best score stored
+    /// for `problem_id`.
+    pub fn new(bar: ProgressBar, problem_id: ProblemId, end: &End) -> Self {
+        let budgeted = end.progress(&Run::new(0.0)).is_some();
+        let template = if budgeted {
+            "{prefix:>4} [{elapsed_precise}<{eta_precise}] {bar:30} {percent:>3}% {msg}"
+        } else {
+            "{prefix:>4} [{elapsed_precise}] {spinner} iter: {pos} {msg}"
+        };
+        bar.set_style(ProgressStyle::with_template(template).unwrap());
+        bar.set_length(if budgeted { BAR_LEN } else { 0 });
+        bar.set_position(0);
+        bar.set_prefix(format!("#{problem_id}"));
+        bar.reset_elapsed();
+        bar.reset_eta();
+        Progress {
+            bar,
+            stored_best: BestScore::new().ok().and_then(|b| b.score(problem_id)),
+        }
+    }
+
+    pub fn update(
+        &self,
+        end: &End,
+        run: &Run,
+        sc: Score,
+        best: Score,
+        temp: f64,
+        accept_rate: f64,
+    ) {
+        match end.progress(run) {
+            Some(done) => self
+                .bar
+                .set_position((done.clamp(0.0, 1.0) * BAR_LEN as f64) as u64),
+            None => self.bar.set_position(run.niter() as u64),
+        }
+        let gain = match self.stored_best {
+            Some(stored) => format!("{:+.0}", best - stored),
+            None => "-".to_string(),
+        };
+        self.bar.set_message(format!(
+            "sc: {sc:.0}, best: {best:.0}, temp: {temp:.1}, accept: {:.1}%, gain: {gain}",
+            accept_rate * 100.0
+        ));
+    }
+
+    /// Prints a line without breaking the bar.
+    pub fn println(&self, line: impl AsRef<str>) {
+        self.bar.suspend(|| println!("{}", line.as_ref()));
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish();
+    }
+}
+
+/// One progress line per worker of a batch run.
+pub struct Workers {
+    _multi: MultiProgress,
+    bars: Vec<ProgressBar>,
+}
+
+impl Workers {
+    pub fn new(n: usize) -> Self {
+        let multi = MultiProgress::new();
+        let bars = (0..n).map(|_| multi.add(ProgressBar::new(0))).collect();
+        Workers {
+            _multi: multi,
+            bars,
+        }
+    }
+
+    /// The bar of the current rayon worker.
+    pub fn bar(&self) -> ProgressBar {
+        let index = rayon::current_thread_index().unwrap_or(0);
+        self.bars[index % self.bars.len()].clone()
+    }
+}
@@ -0,0 +1,224 @@
+use crate::prelude::*;
+
+use crate::draw;
+use crate::history::History;
+use crate::meta::{self, Meta};
+use crate::problem::*;
+use crate::solution::*;
+use crate::stats::UpperBound;
+
+// Results report: {out_dir}/report.html and {out_dir}/report.md, which both
+// link to {out_dir}/svg/{id}.svg.
+
+struct Row {
+    id: ProblemId,
+    spec: Spec,
+    bound: UpperBound,
+    solver: Option<String>,
+    // Seconds since the Unix epoch.
+    improved: Option<u64>,
+}
+
+// The solver and the time of the best solution, from its sidecar, the history
+// of runs, or git.
+fn provenance(
+    id: ProblemId,
+    best: Option<Score>,
+    history: &History,
+) -> (Option<String>, Option<u64>) {
+    if let Ok(meta) = Meta::read(&format!("solution/best/{id}.json")) {
+        return (Some(meta.solver_name), Some(meta.timestamp));
+    }
+    let run = history
+        .entries(id)
+        .into_iter()
+        .find(|e| Some(e.meta.score) == best);
+    match run {
+        Some(e) => (Some(e.meta.solver_name.clone()), Some(e.meta.timestamp)),
+        None => (
+            None,
+            meta::git_commit_time(&format!("solution/best/{id}.json")),
+        ),
+    }
+}
+
+fn rows() -> Result<Vec<Row>> {
+    let history = History::new()?;
+    Ok(UpperBound::all()?
+        .into_iter()
+        .map(|bound| {
+            let (solver, improved) = provenance(bound.id, bound.best, &history);
+            Row {
+                id: bound.id,
+                spec: bound.id.into(),
+                bound,
+                solver,
+                improved,
+            }
+        })
+        .collect())
+}
+
+struct Subtotal {
+    name: &'static str,
+    best: Score,
+    bound: Score,
+}
+
+impl Subtotal {
+    fn new(name: &'static str, rows: &[&Row]) -> Self {
+        Subtotal {
+            name,
+            best: rows.iter().flat_map(|r| r.bound.best).sum(),
+            bound: rows.iter().map(|r| r.bound.bound).sum(),
+        }
+    }
+
+    fn gap(&self) -> Score {
+        (self.bound - self.best) / self.bound.abs().max(1.0) * 100.0
+    }
+}
+
+fn subtotals(rows: &[Row]) -> Vec<Subtotal> {
+    let of = |spec: Option<Spec>| -> Vec<&Row> {
+        rows.iter()
+            .filter(|r| spec.is_none() || spec == Some(r.spec))
+            .collect()
+    };
+    vec![
+        Subtotal::new("V1", &of(Some(Spec::V1))),
+        Subtotal::new("V2", &of(Some(Spec::V2))),
+        Subtotal::new("Total", &of(None)),
+    ]
+}
+
+fn fmt_score(score: Option<Score>) -> String {
+    score.map_or("-".to_string(), |s| format!("{s:.0}"))
+}
+
+fn fmt_gap(gap: Option<Score>) -> String {
+    gap.map_or("-".to_string(), |g| format!("{g:.2}%"))
+}
+
+fn fmt_date(timestamp: Option<u64>) -> String {
+    timestamp.map_or("-".to_string(), meta::format_timestamp)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn thumbnail(id: ProblemId) -> Result<svg::Document> {
+    let problem = Problem::new(id)?;
+    let solution = Solution::best(id).ok();
+    Ok(draw::svg_document(&problem, solution.as_ref()))
+}
+
+fn markdown(rows: &[Row], generated: u64) -> String {
+    let mut md = format!(
+        "# Results\n\nGenerated at {}.\n\n",
+        meta::format_timestamp(generated)
+    );
+    md += "| spec | best | bound | gap |\n|---|--:|--:|--:|\n";
+    for t in subtotals(rows) {
+        md += &format!(
+            "| {} | {:.0} | {:.0} | {:.2}% |\n",
+            t.name,
+            t.best,
+            t.bound,
+            t.gap()
+        );
+    }
+    md += "\n| id | spec | solution | best | bound | gap | solver | improved |\n";
+    md += "|--:|---|---|--:|--:|--:|---|---|\n";
+    for r in rows {
+        md += &format!(
+            "| {} | {:?} | ![{}](svg/{}.svg) | {} | {:.0} | {} | {} | {} |\n",
+            r.id,
+            r.spec,
+            r.id,
+            r.id,
+            fmt_score(r.bound.best),
+            r.bound.bound,
+            fmt_gap(r.bound.gap()),
+            r.solver.as_deref().unwrap_or("-"),
+            fmt_date(r.improved),
+        );
+    }
+    md
+}
+
+fn html(rows: &[Row], generated: u64) -> String {
+    let mut html = String::from(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Results</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; }
+td.num { text-align: right; font-family: monospace; }
+td.thumb img { width: 160px; height: 160px; }
+</style>
+</head>
+<body>
+<h1>Results</h1>
+"#,
+    );
+    html += &format!(
+        "<p>Generated at {}.</p>\n",
+        meta::format_timestamp(generated)
+    );
+    html += "<table>\n<tr><th>spec</th><th>best</th><th>bound</th><th>gap</th></tr>\n";
+    for t in subtotals(rows) {
+        html += &format!(
+            "<tr><td>{}</td><td class=\"num\">{:.0}</td><td class=\"num\">{:.0}</td><td class=\"num\">{:.2}%</td></tr>\n",
+            t.name,
+            t.best,
+            t.bound,
+            t.gap()
+        );
+    }
+    html += "</table>\n<table>\n<tr><th>id</th><th>spec</th><th>solution</th><th>best</th><th>bound</th><th>gap</th><th>solver</th><th>improved</th></tr>\n";
+    for r in rows {
+        html += &format!(
+            "<tr><td class=\"num\">{}</td><td>{:?}</td><td class=\"thumb\"><img src=\"svg/{}.svg\" alt=\"{}\"></td><td class=\"num\">{}</td><td class=\"num\">{:.0}</td><td class=\"num\">{}</td><td>{}</td><td>{}</td></tr>\n",
+            r.id,
+            r.spec,
+            r.id,
+            r.id,
+            fmt_score(r.bound.best),
+            r.bound.bound,
+            fmt_gap(r.bound.gap()),
+            escape(r.solver.as_deref().unwrap_or("-")),
+            fmt_date(r.improved),
+        );
+    }
+    html += "</table>\n</body>\n</html>\n";
+    html
+}
+
+/// Writes report.html, report.md and svg/{id}.svg into `out_dir`, relative to
+/// the project. Returns the path of report.html.
+pub fn write_report(out_dir: impl AsRef<Path>) -> Result<PathBuf> {
+    use rayon::prelude::*;
+
+    let out_dir = &project_path(out_dir);
+    let rows = rows()?;
+    rows.par_iter().try_for_each(|r| -> Result<()> {
+        let path = out_dir.join(format!("svg/{}.svg", r.id));
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        svg::save(path, &thumbnail(r.id)?)?;
+        Ok(())
+    })?;
+
+    let generated = meta::timestamp();
+    std::fs::write(out_dir.join("report.md"), markdown(&rows, generated))?;
+    let html_path = out_dir.join("report.html");
+    std::fs::write(&html_path, html(&rows, generated))?;
+    Ok(html_path)
+}
@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::problem::ProblemId;
+use crate::problem::{Problem, ProblemId};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Solution {
@@ -16,20 +16,66 @@ impl Solution {
     }
 
     pub fn submission(id: ProblemId) -> Result<Solution> {
-        let s = read_from(&format!("solution/submission/{}.json", id))?;
-        let solution: Solution = serde_json::from_str(&s).unwrap();
-        Ok(solution)
+        let path = format!("solution/submission/{}.json", id);
+        let s = read_from(&path)?;
+        serde_json::from_str(&s).with_context(|| format!("parse {path}"))
     }
 
     pub fn best(id: ProblemId) -> Result<Solution> {
-        let s = read_from(&format!("solution/best/{}.json", id))?;
-        let solution: Solution = serde_json::from_str(&s).unwrap();
-        Ok(solution)
+        let path = format!("solution/best/{}.json", id);
+        let s = read_from(&path)?;
+        serde_json::from_str(&s).with_context(|| format!("parse {path}"))
+    }
+
+    /// Checks the rules which the judge checks.
+    pub fn validate(&self, problem: &Problem) -> Result<()> {
+        let nm = problem.musicians.len();
+        ensure!(
+            self.placements.len() == nm,
+            "{} placements for {nm} musicians",
+            self.placements.len()
+        );
+        ensure!(
+            self.volumes.len() == nm,
+            "{} volumes for {nm} musicians",
+            self.volumes.len()
+        );
+        for (i, p) in self.placements.iter().enumerate() {
+            ensure!(problem.on_stage(*p), "musician {i} is off the stage: {p}");
+            for (j, q) in self.placements.iter().enumerate().take(i) {
+                ensure!(
+                    p.distance_squared(*q) >= MUSICIAN_RADIUS_2,
+                    "musicians {j} and {i} are too close: {q}, {p}"
+                );
+            }
+        }
+        for (i, v) in self.volumes.iter().enumerate() {
+            ensure!(
+                (0.0..=MAX_VOLUME).contains(v),
+                "volume of musician {i} is out of range: {v}"
+            );
+        }
+        Ok(())
     }
 
     pub fn from(path: impl AsRef<Path>) -> Result<Solution> {
-        let s = std::fs::read_to_string(path)?;
-        let solution: Solution = serde_json::from_str(&s).unwrap();
-        Ok(solution)
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).with_context(|| format!("read {path:?}"))?;
+        serde_json::from_str(&s).with_context(|| format!("parse {path:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_example() -> Result<()> {
+        let problem = Problem::example()?;
+        let mut solution = Solution::example()?;
+        solution.validate(&problem)?;
+        solution.placements[1] = Point::new(1100.0, 145.0);
+        assert!(solution.validate(&problem).is_err());
+        Ok(())
     }
 }
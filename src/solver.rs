@@ -1,13 +1,58 @@
 use crate::draw;
+use crate::history::{Entry, History};
+use crate::meta::{self, Meta};
 use crate::prelude::*;
 
 use crate::problem::*;
 use crate::solution::*;
 use crate::stats::*;
 
+static INTERRUPTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// On the first SIGINT/SIGTERM, solvers stop at their next check and return
+/// the best solution so far, which is saved as usual. The second one aborts.
+pub fn handle_interrupt() -> Result<()> {
+    use std::sync::atomic::Ordering;
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            eprintln!("Interrupted again. Aborting.");
+            std::process::exit(130);
+        }
+        eprintln!("Interrupted. Saving the best solution so far... (Ctrl-C again to abort)");
+    })?;
+    Ok(())
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Seed of the random number generators of solvers.
+pub const SEED: u64 = 0;
+
+/// `seeded_rng(0)` is `from_seed([0; 32])`.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    let mut bytes = [0; 32];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    SeedableRng::from_seed(bytes)
+}
+
 pub trait Solver {
     fn problem_id(&self) -> ProblemId;
     fn name(&self) -> String;
+    /// Solvers which report progress show it on `bar`.
+    fn set_progress_bar(&mut self, _bar: ProgressBar) {}
+    /// Parameters recorded in the metadata of solutions.
+    fn params(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+    /// The solution which the solver starts from.
+    fn parent(&self) -> Option<&Solution> {
+        None
+    }
     fn solve(&mut self) -> Result<Solved>;
 }
 
@@ -17,14 +62,53 @@ pub struct Solved {
     pub score: Score,
     pub placements: Vec<Point>,
     pub volumes: Vec<Score>,
+    pub niter: Option<usize>,
+    pub meta: Option<Meta>,
 }
 
 impl Solved {
+    pub(crate) fn set_meta<T: Solver + ?Sized>(
+        &mut self,
+        solver: &T,
+        runtime: std::time::Duration,
+    ) -> Result<()> {
+        let problem = Problem::new(self.problem_id)?;
+        let solution = self.solution();
+        self.meta = Some(Meta {
+            problem_id: self.problem_id,
+            problem_hash: meta::problem_hash(self.problem_id)?,
+            solver_name: self.solver_name.clone(),
+            params: solver.params(),
+            seed: solver.seed(),
+            niter: self.niter,
+            runtime_secs: runtime.as_secs_f64(),
+            score: self.score,
+            exact_score: crate::solver_sa::exact_score(
+                &problem,
+                self.problem_id,
+                self.problem_id.into(),
+                &solution,
+            ),
+            solution_hash: meta::solution_hash(&solution),
+            parent_hash: solver.parent().map(meta::solution_hash),
+            git_revision: meta::git_revision(),
+            timestamp: meta::timestamp(),
+        });
+        Ok(())
+    }
+
     fn save_solution(&self) -> Result<()> {
-        self.save_solution_to(&format!(
+        let path = format!(
             "solution/all/{}-{}-{}.json",
             self.problem_id, self.solver_name, self.score
-        ))?;
+        );
+        self.save_solution_to(&path)?;
+        if let Some(meta) = &self.meta {
+            History::append(&Entry {
+                path,
+                meta: meta.clone(),
+            })?;
+        }
 
         self.save_solution_to(&format!(
             "solution/{}/{}.json",
@@ -34,43 +118,36 @@ impl Solved {
 
     fn save_solution_to(&self, name: &str) -> Result<()> {
         let json = serde_json::to_string(&self.solution())?;
-        write_to(name, &json)
+        write_to(name, &json)?;
+        if let Some(meta) = &self.meta {
+            write_to(Meta::path(name), &serde_json::to_string_pretty(meta)?)?;
+        }
+        Ok(())
     }
 
     pub fn save_best_if(&self) -> Result<()> {
-        let best_score = BestScore::new()?;
-        let is_best = match best_score.score(self.problem_id) {
-            Some(best) => {
-                if best < self.score {
-                    println!(
-                        "💘 problem_id: {}, best: {best} < score: {}",
-                        self.problem_id, self.score
-                    );
-                    true
-                } else {
-                    info!(
-                        "😢 problem_id: {}, best: {best} >= score: {}",
-                        self.problem_id, self.score,
-                    );
-                    false
-                }
-            }
-            None => {
-                println!(
-                    "💘 problem_id: {}, best: None < score: {}",
-                    self.problem_id, self.score
-                );
-                true
-            }
-        };
-        if is_best {
-            self.save_solution_to(&format!("solution/best/{}.json", self.problem_id))?;
-            BestScore::update(self.problem_id, self.score)?;
+        let (best, promoted) = BestScore::promote(
+            self.problem_id,
+            self.score,
+            &self.solution(),
+            self.meta.as_ref(),
+        )?;
+        let best = best.map_or("None".to_string(), |best| best.to_string());
+        if promoted {
+            println!(
+                "💘 problem_id: {}, best: {best} < score: {}",
+                self.problem_id, self.score
+            );
+        } else {
+            info!(
+                "😢 problem_id: {}, best: {best} >= score: {}",
+                self.problem_id, self.score,
+            );
         }
         Ok(())
     }
 
-    fn solution(&self) -> Solution {
+    pub fn solution(&self) -> Solution {
         Solution {
             placements: self.placements.clone(),
             volumes: self.volumes.clone(),
@@ -86,14 +163,22 @@ impl Solved {
     }
 }
 
-pub fn solve<T: Solver>(mut solver: T) -> Result<()> {
-    println!("Solving... {}", solver.problem_id());
-    let solved = solver.solve()?;
-    println!("Solved {}. score: {}", solver.problem_id(), solved.score);
-    solved.save_solution()?;
-    solved.save_best_if()?;
-    solved.plot()?;
-    Ok(())
+pub fn solve<T: Solver>(solver: T) -> Result<()> {
+    solve_with_progress(solver, ProgressBar::new(0))
+}
+
+pub fn solve_with_progress<T: Solver>(mut solver: T, bar: ProgressBar) -> Result<()> {
+    bar.suspend(|| println!("Solving... {}", solver.problem_id()));
+    solver.set_progress_bar(bar.clone());
+    let start = std::time::Instant::now();
+    let mut solved = solver.solve()?;
+    solved.set_meta(&solver, start.elapsed())?;
+    bar.suspend(|| {
+        println!("Solved {}. score: {}", solver.problem_id(), solved.score);
+        solved.save_solution()?;
+        solved.save_best_if()?;
+        solved.plot()
+    })
 }
 
 #[cfg(test)]
@@ -0,0 +1,372 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+use crate::solver::*;
+use crate::solver_sa::LocalState;
+
+// Branch and bound over candidate points for problems with a few musicians.
+//
+// A musician on candidate `c` can't earn more than `positive_impact(c)` times
+// the max volume and the closeness cap, whatever the others do, so the sum of
+// those per-candidate bounds prunes partial assignments.
+
+pub const MAX_MUSICIANS: usize = 12;
+
+/// What the branch and bound proved.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Certificate {
+    pub problem_id: ProblemId,
+    pub grid: Coord,
+    pub num_candidates: usize,
+    pub num_nodes: usize,
+    /// Whether the search space was exhausted within `max_nodes`.
+    pub complete: bool,
+    pub best: Score,
+    /// Upper bound of the score of any assignment to the candidate points.
+    pub bound: Score,
+    /// Upper bound of the score of any solution (`Problem::upper_bound`).
+    pub continuous_bound: Score,
+}
+
+struct Search<'a> {
+    problem: &'a Problem,
+    spec: Spec,
+    candidates: Vec<Point>,
+    // Musicians sorted by instrument.
+    order: Vec<usize>,
+    // For each instrument, candidate indices and their bounds, best first.
+    sorted: HashMap<Instrument, Vec<(usize, Score)>>,
+    // Bound of musicians in instrument groups after order[level]'s group.
+    tail: Vec<Score>,
+    max_nodes: usize,
+    num_nodes: usize,
+    open_bound: Score,
+    best: Score,
+    best_placements: Option<Vec<Point>>,
+    placements: Vec<Point>,
+}
+
+impl<'a> Search<'a> {
+    fn new(problem: &'a Problem, spec: Spec, grid: Coord, max_nodes: usize) -> Self {
+        // Candidates are evenly spaced and include the edges of the stage.
+        let axis = |min: Coord, len: Coord| -> Vec<Coord> {
+            let (min, max) = (min + MUSICIAN_RADIUS, min + len - MUSICIAN_RADIUS);
+            let n = ((max - min) / grid).ceil().max(1.0) as usize;
+            (0..=n)
+                .map(|i| min + (max - min) * i as Coord / n as Coord)
+                .collect()
+        };
+        let xs = axis(problem.stage_bottom_left[0], problem.stage_width);
+        let ys = axis(problem.stage_bottom_left[1], problem.stage_height);
+        let candidates = xs
+            .iter()
+            .flat_map(|&x| ys.iter().map(move |&y| Point::new(x, y)))
+            .filter(|&p| problem.on_stage(p))
+            .collect::<Vec<_>>();
+
+        let inst_cnt = inst_cnt(&problem.musicians);
+        let sorted = inst_cnt
+            .iter()
+            .map(|(&inst, &cnt)| {
+                let q = match spec {
+                    Spec::V1 => 1.0,
+                    Spec::V2 => closeness_cap(cnt),
+                };
+                let mut ubs = candidates
+                    .iter()
+                    .enumerate()
+                    .map(|(c, p)| (c, problem.positive_impact(inst, *p) * q * MAX_VOLUME))
+                    .collect::<Vec<_>>();
+                ubs.sort_by_key(|(_, ub)| std::cmp::Reverse(OrderedFloat(*ub)));
+                (inst, ubs)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut order = (0..problem.musicians.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| problem.musicians[i]);
+
+        let group_bound = |inst: Instrument| -> Score {
+            sorted[&inst]
+                .iter()
+                .take(inst_cnt[&inst])
+                .map(|(_, ub)| ub)
+                .sum()
+        };
+        let mut tail = vec![0.0; order.len()];
+        for level in (0..order.len()).rev() {
+            let inst = problem.musicians[order[level]];
+            tail[level] = match order.get(level + 1) {
+                None => 0.0,
+                Some(&next) if problem.musicians[next] == inst => tail[level + 1],
+                Some(&next) => tail[level + 1] + group_bound(problem.musicians[next]),
+            };
+        }
+
+        Search {
+            problem,
+            spec,
+            candidates,
+            order,
+            sorted,
+            tail,
+            max_nodes,
+            num_nodes: 0,
+            open_bound: Score::NEG_INFINITY,
+            best: Score::NEG_INFINITY,
+            best_placements: None,
+            placements: vec![Point::new(0.0, 0.0); problem.musicians.len()],
+        }
+    }
+
+    // The score with the best volumes. Direct O(nm^2 * natt) evaluation,
+    // which is much faster than `LocalState` for a few musicians.
+    fn evaluate(&mut self) {
+        let problem = self.problem;
+        let place = &self.placements;
+        let nm = place.len();
+        let full_round = matches!(self.spec, Spec::V2);
+
+        let score = (0..nm)
+            .map(|i| {
+                let inst = problem.musicians[i];
+                let impact = problem
+                    .attendees
+                    .iter()
+                    .filter(|a| {
+                        let blocked = (0..nm).any(|j| {
+                            i != j
+                                && is_line_circle_intersect(
+                                    place[i],
+                                    a.point(),
+                                    place[j],
+                                    BLOCK_RADIUS,
+                                )
+                        }) || (full_round
+                            && problem.pillars.iter().any(|p| {
+                                is_line_circle_intersect(
+                                    place[i],
+                                    a.point(),
+                                    p.center_point(),
+                                    p.radius,
+                                )
+                            }));
+                        !blocked
+                    })
+                    .map(|a| 1e6 * a.tastes[inst] / place[i].distance_squared(a.point()))
+                    .sum::<Score>();
+                let q = if full_round {
+                    1.0 + (0..nm)
+                        .filter(|&j| i != j && problem.musicians[j] == inst)
+                        .map(|j| 1.0 / place[i].distance(place[j]))
+                        .sum::<Score>()
+                } else {
+                    1.0
+                };
+                (q * impact).max(0.0) * MAX_VOLUME
+            })
+            .sum::<Score>();
+
+        if score > self.best {
+            self.best = score;
+            self.best_placements = Some(self.placements.clone());
+        }
+    }
+
+    // `start` is the first index in `sorted` which the musician at `level`
+    // can take, so that musicians of the same instrument take candidates in
+    // increasing order.
+    fn dfs(&mut self, level: usize, start: usize, partial: Score) {
+        if level == self.order.len() {
+            self.evaluate();
+            return;
+        }
+
+        let m = self.order[level];
+        let inst = self.problem.musicians[m];
+        // Musicians of `inst` left including `m`.
+        let rest = self.order[level..]
+            .iter()
+            .take_while(|&&i| self.problem.musicians[i] == inst)
+            .count();
+
+        let len = self.sorted[&inst].len();
+        let mut window = self.sorted[&inst][start.min(len)..]
+            .iter()
+            .take(rest)
+            .map(|(_, ub)| ub)
+            .sum::<Score>();
+
+        for t in start..len {
+            if len - t < rest {
+                break;
+            }
+            let bound = partial + window + self.tail[level];
+            if bound <= self.best {
+                break;
+            }
+            if self.num_nodes >= self.max_nodes {
+                self.open_bound = self.open_bound.max(bound);
+                return;
+            }
+            self.num_nodes += 1;
+
+            let (c, ub) = self.sorted[&inst][t];
+            let p = self.candidates[c];
+            let collides = self.order[..level]
+                .iter()
+                .any(|&j| p.distance_squared(self.placements[j]) < MUSICIAN_RADIUS_2 + EPS);
+            if !collides {
+                self.placements[m] = p;
+                let next_start = if rest > 1 { t + 1 } else { 0 };
+                self.dfs(level + 1, next_start, partial + ub);
+            }
+
+            let sorted = &self.sorted[&inst];
+            window -= sorted[t].1;
+            if let Some((_, ub)) = sorted.get(t + rest) {
+                window += ub;
+            }
+        }
+    }
+}
+
+pub fn run_exact(
+    problem: &Problem,
+    problem_id: ProblemId,
+    spec: Spec,
+    grid: Coord,
+    max_nodes: usize,
+) -> Result<(Solution, Certificate)> {
+    ensure!(
+        problem.musicians.len() <= MAX_MUSICIANS,
+        "too many musicians for exact search: {}",
+        problem.musicians.len()
+    );
+    ensure!(grid > 0.0, "grid must be positive: {grid}");
+    ensure!(
+        problem.on_stage(problem.stage_center()),
+        "no room on the stage"
+    );
+
+    let mut search = Search::new(problem, spec, grid, max_nodes);
+    search.dfs(0, 0, 0.0);
+
+    let Some(placements) = search.best_placements.clone() else {
+        bail!("no valid assignment on the candidate points");
+    };
+    let solution = LocalState::new(
+        problem,
+        problem_id,
+        spec,
+        &Solution {
+            volumes: vec![MAX_VOLUME; placements.len()],
+            placements,
+        },
+    )
+    .to_solution();
+
+    let certificate = Certificate {
+        problem_id,
+        grid,
+        num_candidates: search.candidates.len(),
+        num_nodes: search.num_nodes,
+        complete: search.open_bound == Score::NEG_INFINITY,
+        best: search.best,
+        bound: search.best.max(search.open_bound),
+        continuous_bound: problem.upper_bound(spec),
+    };
+    Ok((solution, certificate))
+}
+
+pub struct SolverExact {
+    problem_id: ProblemId,
+    problem: Problem,
+    grid: Coord,
+    max_nodes: usize,
+}
+
+impl SolverExact {
+    pub fn new(problem_id: ProblemId, grid: Coord, max_nodes: usize) -> Result<Self> {
+        let problem = Problem::new(problem_id)?;
+        Ok(Self {
+            problem_id,
+            problem,
+            grid,
+            max_nodes,
+        })
+    }
+}
+
+impl Solver for SolverExact {
+    fn problem_id(&self) -> ProblemId {
+        self.problem_id
+    }
+
+    fn name(&self) -> String {
+        format!("exact-grid-{}-nodes-{}", self.grid, self.max_nodes)
+    }
+
+    fn params(&self) -> serde_json::Value {
+        serde_json::json!({ "grid": self.grid, "max_nodes": self.max_nodes })
+    }
+
+    fn solve(&mut self) -> Result<Solved> {
+        let (solution, certificate) = run_exact(
+            &self.problem,
+            self.problem_id,
+            self.problem_id.into(),
+            self.grid,
+            self.max_nodes,
+        )?;
+        println!(
+            "certificate: complete: {}, best: {}, bound on candidates: {}, bound: {}",
+            certificate.complete, certificate.best, certificate.bound, certificate.continuous_bound
+        );
+        write_to(
+            format!("stats/exact/{}-{}.json", self.problem_id, self.name()),
+            &serde_json::to_string(&certificate)?,
+        )?;
+
+        let score = crate::solver_sa::score(
+            &self.problem,
+            self.problem_id,
+            self.problem_id.into(),
+            &solution,
+        );
+        let Solution {
+            placements,
+            volumes,
+        } = solution;
+        Ok(Solved {
+            problem_id: self.problem_id,
+            solver_name: self.name(),
+            score,
+            placements,
+            volumes,
+            niter: Some(certificate.num_nodes),
+            meta: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_certificate() -> Result<()> {
+        let id = 42;
+        let problem = Problem::new(id)?;
+        let (solution, certificate) = run_exact(&problem, id, id.into(), 60.0, 1_000_000)?;
+        assert!(certificate.complete);
+        assert!(certificate.best <= certificate.bound);
+        assert!(certificate.bound <= certificate.continuous_bound);
+        assert_relative_eq!(
+            crate::solver_sa::score(&problem, id, id.into(), &solution),
+            certificate.best,
+            max_relative = 1e-9
+        );
+        Ok(())
+    }
+}
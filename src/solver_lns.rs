@@ -0,0 +1,376 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::progress::Progress;
+use crate::solution::*;
+use crate::solver::*;
+use crate::solver_sa::{End, LocalState, Run, SolverSa};
+
+// Large neighborhood search: remove k musicians and reinsert them greedily.
+
+// Candidate positions tried for each reinserted musician, besides its old
+// position. If every candidate collides, `run_lns` restores the neighborhood,
+// so the state is always valid.
+const NUM_CANDIDATES: usize = 8;
+
+// Removed musicians wait around here, far outside the room, where they are
+// barely heard and block no one, so that they free their space for each other.
+const PARKING: Coord = -1e9;
+
+// Jitter moves tried for each reinserted musician after the greedy repair.
+const NUM_INNER_MOVES: usize = 4;
+
+enum Destroy {
+    // Musicians nearest to a random point on the stage.
+    Window(Point),
+    // Musicians playing the same instrument.
+    Instrument,
+}
+
+fn destroy(st: &LocalState, rng: &mut StdRng, k: usize) -> (Destroy, Vec<usize>) {
+    let nm = st.place.len();
+    let k = k.min(nm);
+    if rng.gen_range(0..2) == 0 {
+        let center = st.problem.random_point_on_stage(rng);
+        let mut ids = (0..nm).collect::<Vec<_>>();
+        ids.sort_by_key(|&i| OrderedFloat(st.place[i].distance_squared(center)));
+        ids.truncate(k);
+        (Destroy::Window(center), ids)
+    } else {
+        let inst = st.problem.musicians[rng.gen_range(0..nm)];
+        let mut ids = (0..nm)
+            .filter(|&i| st.problem.musicians[i] == inst)
+            .collect::<Vec<_>>();
+        // Partial Fisher-Yates shuffle.
+        for i in 0..k.min(ids.len()) {
+            let j = rng.gen_range(i..ids.len());
+            ids.swap(i, j);
+        }
+        ids.truncate(k);
+        (Destroy::Instrument, ids)
+    }
+}
+
+fn candidate(st: &LocalState, rng: &mut StdRng, destroy: &Destroy, radius: Coord) -> Point {
+    match destroy {
+        Destroy::Window(center) => {
+            let dist = radius * rng.gen_range(0.0f64..1.0).sqrt();
+            let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
+            Point::new(center.x + dist * angle.cos(), center.y + dist * angle.sin())
+        }
+        Destroy::Instrument => st.problem.random_point_on_stage(rng),
+    }
+}
+
+// Takes musicians `ids` out of the stage.
+fn park(st: &mut LocalState, ids: &[usize]) {
+    for (k, &i) in ids.iter().enumerate() {
+        let x = PARKING - 2.0 * MUSICIAN_RADIUS * k as Coord;
+        st.do_move(i, Point::new(x, PARKING));
+    }
+}
+
+// Moves parked musician `i` to the best of `candidates`. False if all of them
+// collide, and `i` stays parked.
+fn insert(st: &mut LocalState, i: usize, candidates: impl Iterator<Item = Point>) -> bool {
+    let mut best = (Score::NEG_INFINITY, None);
+    for p in candidates {
+        if st.collides(i, p) {
+            continue;
+        }
+        st.do_move(i, p);
+        if st.score > best.0 {
+            best = (st.score, Some(p));
+        }
+    }
+    match best.1 {
+        Some(p) => {
+            st.do_move(i, p);
+            true
+        }
+        None => false,
+    }
+}
+
+// Moves musician `i` to the best of `candidates` (and its current position).
+fn reinsert(st: &mut LocalState, i: usize, candidates: impl Iterator<Item = Point>) {
+    let mut best = (st.score, st.place[i]);
+    for p in candidates {
+        if st.collides(i, p) {
+            continue;
+        }
+        st.do_move(i, p);
+        if st.score > best.0 {
+            best = (st.score, p);
+        }
+    }
+    st.do_move(i, best.1);
+}
+
+// Reinserts parked musicians `ids`, which were at `old`. False if one of them
+// can't be placed.
+fn repair(
+    st: &mut LocalState,
+    rng: &mut StdRng,
+    destroy: &Destroy,
+    ids: &[usize],
+    old: &[Point],
+) -> bool {
+    // The radius of the window which covers removed musicians.
+    let radius = match destroy {
+        Destroy::Window(center) => old
+            .iter()
+            .map(|p| p.distance(*center))
+            .fold(MUSICIAN_RADIUS, f64::max),
+        Destroy::Instrument => 0.0,
+    };
+
+    for (&i, &p0) in ids.iter().zip(old) {
+        let candidates = (0..NUM_CANDIDATES)
+            .map(|_| candidate(st, rng, destroy, radius))
+            .chain([p0])
+            .collect::<Vec<_>>();
+        if !insert(st, i, candidates.into_iter()) {
+            return false;
+        }
+    }
+
+    // Short inner search.
+    for &i in ids {
+        let p0 = st.place[i];
+        let candidates = (0..NUM_INNER_MOVES)
+            .map(|_| {
+                let dist = 20.0 * rng.gen_range(0.0f64..1.0).powi(2);
+                let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
+                Point::new(p0.x + dist * angle.cos(), p0.y + dist * angle.sin())
+            })
+            .collect::<Vec<_>>();
+        reinsert(st, i, candidates.into_iter());
+    }
+    true
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_lns(
+    rng: &mut StdRng,
+    problem: &Problem,
+    problem_id: ProblemId,
+    spec: Spec,
+    solution: &Solution,
+    k: usize,
+    temp0: Option<f64>,
+    end: &End,
+    progress: &Progress,
+    // Whether to draw the work in progress.
+    draw: bool,
+) -> Result<(Score, Solution, usize)> {
+    let mut st = LocalState::new(problem, problem_id, spec, solution);
+    let mut sc = st.score;
+
+    let mut run = Run::new(sc);
+
+    let temp0 = temp0.unwrap_or_else(|| sc.abs() / (problem.musicians.len() as f64).sqrt());
+    let mut best = sc;
+    let mut best_solution = st.to_solution();
+
+    // Since the last log.
+    let mut naccept = 0;
+    let mut ntried = 0;
+    let mut niter = 0;
+
+    loop {
+        niter += 1;
+
+        run.update(niter, best);
+        if end.is_done(&run) || interrupted() {
+            info!("end: {end}, niter: {niter}, best: {best:.1}");
+            progress.finish();
+            return Ok((best, best_solution, niter));
+        }
+        let temp = temp0 * (1.0 - end.progress(&run).unwrap_or(0.0));
+        progress.update(
+            end,
+            &run,
+            sc,
+            best,
+            temp,
+            naccept as f64 / ntried.max(1) as f64,
+        );
+
+        if niter % 100 == 0 {
+            info!(
+                "temp: {temp:.1}, niter: {niter}, sc: {sc:.1}, best: {best:.1}, naccept: {naccept}"
+            );
+            naccept = 0;
+            ntried = 0;
+        }
+
+        if draw && niter % 1_000 == 0 {
+            st.report_progress()?;
+        }
+
+        let (destroy, ids) = destroy(&st, rng, k);
+        let old = ids.iter().map(|&i| st.place[i]).collect::<Vec<_>>();
+
+        park(&mut st, &ids);
+        let repaired = repair(&mut st, rng, &destroy, &ids, &old);
+
+        let sc2 = st.score;
+        ntried += 1;
+        if repaired && (sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0)) {
+            naccept += 1;
+            sc = sc2;
+            if sc > best {
+                best = sc;
+                best_solution = st.to_solution();
+            }
+        } else {
+            for (&i, &p) in ids.iter().zip(old.iter()).rev() {
+                st.do_move(i, p);
+            }
+        }
+    }
+}
+
+pub struct SolverLns {
+    problem_id: ProblemId,
+    problem: Problem,
+    k: usize,
+    temp0: Option<f64>,
+    end: End,
+    initial_solution: Solution,
+    // Whether `initial_solution` was given, rather than random.
+    warm_start: bool,
+    progress_bar: Option<ProgressBar>,
+    // Whether to write drawings of the work in progress.
+    draw: bool,
+    seed: u64,
+}
+
+impl SolverLns {
+    pub fn new(
+        problem_id: ProblemId,
+        k: usize,
+        temp0: Option<f64>,
+        end: End,
+        initial_solution: Option<Solution>,
+    ) -> Result<Self> {
+        let problem = Problem::new(problem_id)?;
+        let warm_start = initial_solution.is_some();
+        let initial_solution =
+            initial_solution.unwrap_or_else(|| SolverSa::initial_solution(&problem));
+        Ok(Self {
+            problem_id,
+            problem,
+            k,
+            temp0,
+            end,
+            initial_solution,
+            warm_start,
+            progress_bar: None,
+            draw: true,
+            seed: SEED,
+        })
+    }
+
+    /// Writes no drawings of the work in progress.
+    pub fn hide_drawing(&mut self) {
+        self.draw = false;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+}
+
+impl Solver for SolverLns {
+    fn problem_id(&self) -> ProblemId {
+        self.problem_id
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "lns-k-{}-temp0-{:.0}-{}",
+            self.k,
+            self.temp0.unwrap_or(0.0),
+            self.end
+        )
+    }
+
+    fn set_progress_bar(&mut self, bar: ProgressBar) {
+        self.progress_bar = Some(bar);
+    }
+
+    fn params(&self) -> serde_json::Value {
+        serde_json::json!({
+            "k": self.k,
+            "temp0": self.temp0,
+            "end": self.end.to_string(),
+        })
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+
+    fn parent(&self) -> Option<&Solution> {
+        self.warm_start.then_some(&self.initial_solution)
+    }
+
+    fn solve(&mut self) -> Result<Solved> {
+        let mut rng = seeded_rng(self.seed);
+        let progress = match &self.progress_bar {
+            Some(bar) => Progress::new(bar.clone(), self.problem_id, &self.end),
+            None => Progress::hidden(),
+        };
+
+        let (score, solution, niter) = run_lns(
+            &mut rng,
+            &self.problem,
+            self.problem_id,
+            self.problem_id.into(),
+            &self.initial_solution,
+            self.k,
+            self.temp0,
+            &self.end,
+            &progress,
+            self.draw,
+        )?;
+        let Solution {
+            placements,
+            volumes,
+        } = solution;
+        Ok(Solved {
+            problem_id: self.problem_id,
+            solver_name: self.name(),
+            score,
+            placements,
+            volumes,
+            niter: Some(niter),
+            meta: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solver_lns_improves() -> Result<()> {
+        let id = 42;
+        let problem = Problem::new(id)?;
+        let initial = SolverSa::initial_solution(&problem);
+        let initial_score = crate::solver_sa::score(&problem, id, id.into(), &initial);
+        let mut solver = SolverLns::new(id, 3, Some(0.0), End::MaxIteration(20), Some(initial))?;
+        solver.hide_drawing();
+        let solved = solver.solve()?;
+        assert!(
+            solved.score > initial_score,
+            "{} <= {initial_score}",
+            solved.score
+        );
+        solved.solution().validate(&problem)?;
+        Ok(())
+    }
+}
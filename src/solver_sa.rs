@@ -3,9 +3,12 @@ use rand::rngs::StdRng;
 use crate::prelude::*;
 
 use crate::draw;
+use crate::movelog::{Change, Header, MoveLog};
 use crate::problem::*;
+use crate::progress::Progress;
 use crate::solution::*;
 use crate::solver::*;
+use crate::trace::{self, MoveKind, Moves, Record, Trace};
 use rand::Rng;
 
 // https://gitlab.com/rafaelbocquet-cpcontests/icfpc23/-/blob/main/cxx/solve.cpp
@@ -20,15 +23,17 @@ struct AttNode {
     y: Coord,
 }
 
-struct LocalState<'a> {
+pub(crate) struct LocalState<'a> {
     problem_id: ProblemId,
-    problem: &'a Problem,
+    pub(crate) problem: &'a Problem,
     spec: Spec,
-    place: Vec<Point>,
+    pub(crate) place: Vec<Point>,
     q: Vec<Score>,
     angles: Vec<Vec<AttNode>>,
     scores: Vec<Score>,
-    score: Score,
+    // None: each musician plays at the volume which is best for it.
+    volumes: Option<Vec<Volume>>,
+    pub(crate) score: Score,
 }
 
 // problem.hpp
@@ -45,12 +50,72 @@ fn norm_angle(mut angle: f64) -> f64 {
 
 // Score doesn't match judge's one because volumes or q are muliplied at last.
 pub fn score(problem: &Problem, problem_id: ProblemId, spec: Spec, solution: &Solution) -> Score {
-    let st = LocalState::new(problem, problem_id, spec, &solution);
+    let st = LocalState::new(problem, problem_id, spec, solution);
     st.score
 }
 
+/// The score by the judge's formula, which rounds up each impact.
+pub fn exact_score(
+    problem: &Problem,
+    problem_id: ProblemId,
+    spec: Spec,
+    solution: &Solution,
+) -> Score {
+    let st = LocalState::new(problem, problem_id, spec, solution);
+    st.exact_score(&solution.volumes)
+}
+
+/// Which score to compute: `score` is what solvers optimize, and
+/// `exact_score` is what the judge reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scorer {
+    Fast,
+    Exact,
+}
+
+impl Scorer {
+    pub fn score(
+        self,
+        problem: &Problem,
+        problem_id: ProblemId,
+        spec: Spec,
+        solution: &Solution,
+    ) -> Score {
+        match self {
+            Scorer::Fast => score(problem, problem_id, spec, solution),
+            Scorer::Exact => exact_score(problem, problem_id, spec, solution),
+        }
+    }
+}
+
+impl std::fmt::Display for Scorer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scorer::Fast => write!(f, "fast"),
+            Scorer::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+impl std::str::FromStr for Scorer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fast" => Ok(Scorer::Fast),
+            "exact" => Ok(Scorer::Exact),
+            _ => bail!("invalid scorer: {s}"),
+        }
+    }
+}
+
 impl<'a> LocalState<'a> {
-    fn new(problem: &'a Problem, problem_id: ProblemId, spec: Spec, solution: &Solution) -> Self {
+    pub(crate) fn new(
+        problem: &'a Problem,
+        problem_id: ProblemId,
+        spec: Spec,
+        solution: &Solution,
+    ) -> Self {
         let nm = solution.placements.len();
         let natt = problem.attendees.len();
 
@@ -78,6 +143,7 @@ impl<'a> LocalState<'a> {
             q: vec![1.0; nm],
             angles,
             scores: vec![0.0; nm],
+            volumes: None,
             score: 0.0,
         };
 
@@ -87,13 +153,12 @@ impl<'a> LocalState<'a> {
             state.make_angles(i);
         }
 
-        state.update_score(Some(&solution.volumes));
-        // state.update_score(None);
+        state.score = state.score_with(Some(&solution.volumes));
         state
     }
 
-    fn update_score(&mut self, volumes: Option<&[f64]>) {
-        self.score = if let Some(volumes) = volumes {
+    fn score_with(&self, volumes: Option<&[f64]>) -> Score {
+        if let Some(volumes) = volumes {
             (0..self.scores.len())
                 .map(|i| {
                     // self.q[i] * self.scores[i] * volumes[i]
@@ -106,7 +171,82 @@ impl<'a> LocalState<'a> {
             (0..self.scores.len())
                 .map(|i| (self.q[i] * self.scores[i]).max(0.0) * 10.0)
                 .sum()
-        };
+        }
+    }
+
+    // Angle ranges only screen blockers here. Each candidate is checked
+    // against the segment to the attendee as the judge does.
+    fn exact_score(&self, volumes: &[Volume]) -> Score {
+        let attendees = &self.problem.attendees;
+        (0..self.place.len())
+            .map(|i| {
+                let mut blocked = vec![false; attendees.len()];
+                for j in 0..self.place.len() {
+                    if i == j {
+                        continue;
+                    }
+                    let [r1, r2] = self.blocks_range(i, j);
+                    for k in r1.chain(r2) {
+                        let a = self.angles[i][k].index;
+                        blocked[a] = blocked[a]
+                            || is_line_circle_intersect(
+                                self.place[i],
+                                attendees[a].point(),
+                                self.place[j],
+                                BLOCK_RADIUS,
+                            );
+                    }
+                }
+                if self.is_full_round() {
+                    for (j, pillar) in self.problem.pillars.iter().enumerate() {
+                        let [r1, r2] = self.pillars_range(i, j);
+                        for k in r1.chain(r2) {
+                            let a = self.angles[i][k].index;
+                            blocked[a] = blocked[a]
+                                || is_line_circle_intersect(
+                                    self.place[i],
+                                    attendees[a].point(),
+                                    pillar.center_point(),
+                                    pillar.radius,
+                                );
+                        }
+                    }
+                }
+                let inst = self.problem.musicians[i];
+                attendees
+                    .iter()
+                    .zip(blocked)
+                    .filter(|(_, blocked)| !blocked)
+                    .map(|(a, _)| {
+                        let d2 = self.place[i].distance_squared(a.point());
+                        let impact = (1e6 * a.tastes[inst] / d2).ceil();
+                        (self.q[i] * volumes[i] * impact).ceil()
+                    })
+                    .sum::<Score>()
+            })
+            .sum()
+    }
+
+    fn update_score(&mut self) {
+        self.score = self.score_with(self.volumes.as_deref());
+    }
+
+    /// Makes volumes a part of the state. Musicians keep playing at
+    /// `volumes` until `do_volume` changes them.
+    pub(crate) fn set_volumes(&mut self, volumes: Vec<Volume>) {
+        self.volumes = Some(volumes);
+        self.update_score();
+    }
+
+    pub(crate) fn volume(&self, i: usize) -> Option<Volume> {
+        self.volumes.as_ref().map(|volumes| volumes[i])
+    }
+
+    pub(crate) fn do_volume(&mut self, i: usize, volume: Volume) {
+        let volumes = self.volumes.as_mut().expect("volumes are not set");
+        let old = volumes[i];
+        volumes[i] = volume;
+        self.score += self.q[i] * self.scores[i] * (volume - old);
     }
 
     fn cal_q(&mut self) {
@@ -121,19 +261,20 @@ impl<'a> LocalState<'a> {
         }
     }
 
-    fn to_solution(&self) -> Solution {
-        let volumes = self
-            .scores
-            .iter()
-            .map(|score| if *score <= 1e-6 { 0.0 } else { 10.0 })
-            .collect();
+    pub(crate) fn to_solution(&self) -> Solution {
+        let volumes = self.volumes.clone().unwrap_or_else(|| {
+            self.scores
+                .iter()
+                .map(|score| if *score <= 1e-6 { 0.0 } else { 10.0 })
+                .collect()
+        });
         Solution {
             placements: self.place.clone(),
             volumes,
         }
     }
 
-    fn report_progress(&self) -> Result<()> {
+    pub(crate) fn report_progress(&self) -> Result<()> {
         // Save svg for reporting.
         let solution = self.to_solution();
         draw::draw_solution(
@@ -178,7 +319,7 @@ impl<'a> LocalState<'a> {
 
     fn add_blocks(&mut self, i: usize, j: usize) {
         let [r1, r2] = self.blocks_range(i, j);
-        for k in r1.into_iter().chain(r2.into_iter()) {
+        for k in r1.into_iter().chain(r2) {
             if self.angles[i][k].nblock == 0 {
                 let d2 = self.place[i]
                     .distance_squared(self.problem.attendees[self.angles[i][k].index].point());
@@ -193,7 +334,7 @@ impl<'a> LocalState<'a> {
 
     fn rem_blocks(&mut self, i: usize, j: usize) {
         let [r1, r2] = self.blocks_range(i, j);
-        for k in r1.into_iter().chain(r2.into_iter()) {
+        for k in r1.into_iter().chain(r2) {
             self.angles[i][k].nblock -= 1;
             if self.angles[i][k].nblock == 0 {
                 let d2 = self.place[i]
@@ -210,7 +351,7 @@ impl<'a> LocalState<'a> {
         for j in 0..self.problem.pillars.len() {
             let dp2 = self.place[i].distance_squared(self.problem.pillars[j].center_point());
             let [r1, r2] = self.pillars_range(i, j);
-            for k in r1.into_iter().chain(r2.into_iter()) {
+            for k in r1.into_iter().chain(r2) {
                 let d2 = self.place[i]
                     .distance_squared(self.problem.attendees[self.angles[i][k].index].point());
                 if d2 > dp2 {
@@ -242,8 +383,13 @@ impl<'a> LocalState<'a> {
     }
 
     fn blocks_range(&self, i: usize, j: usize) -> [Range<usize>; 2] {
-        let d = self.place[i].distance(self.place[j]);
-        let angle = (self.place[j].y - self.place[i].y).atan2(self.place[j].x - self.place[i].x);
+        self.blocks_range_at(i, self.place[j])
+    }
+
+    // Attendees in `angles[i]` which a musician at `p` blocks from musician `i`.
+    fn blocks_range_at(&self, i: usize, p: Point) -> [Range<usize>; 2] {
+        let d = self.place[i].distance(p);
+        let angle = (p.y - self.place[i].y).atan2(p.x - self.place[i].x);
         let alpha = (BLOCK_RADIUS / d).asin();
         let angle0 = norm_angle(angle - alpha);
         let angle1 = norm_angle(angle + alpha);
@@ -276,7 +422,7 @@ impl<'a> LocalState<'a> {
         }
     }
 
-    fn do_move(&mut self, i: usize, to: Point) {
+    pub(crate) fn do_move(&mut self, i: usize, to: Point) {
         let nm = self.place.len();
 
         for j in 0..nm {
@@ -314,10 +460,10 @@ impl<'a> LocalState<'a> {
             }
         }
 
-        self.update_score(None);
+        self.update_score();
     }
 
-    fn do_swap(&mut self, a: usize, b: usize) {
+    pub(crate) fn do_swap(&mut self, a: usize, b: usize) {
         let nm = self.place.len();
 
         if self.is_full_round() {
@@ -353,7 +499,74 @@ impl<'a> LocalState<'a> {
                 self.q[b] -= qplus;
             }
         }
-        self.update_score(None);
+        self.update_score();
+    }
+
+    /// The score of musician `i` at volume 1.
+    pub(crate) fn contribution(&self, i: usize) -> Score {
+        self.q[i] * self.scores[i]
+    }
+
+    /// The score change if musician `b` moved to `p`, counting only how `b`
+    /// blocks others. The closeness factor and `b`'s own score are ignored,
+    /// which is exact for a silent `b` in V1.
+    pub(crate) fn blocking_gain(&self, b: usize, p: Point) -> Score {
+        let in_ranges =
+            |ranges: &[Range<usize>; 2], k: usize| ranges.iter().any(|r| r.contains(&k));
+        let impact = |i: usize, k: usize| {
+            let a = &self.problem.attendees[self.angles[i][k].index];
+            1e6 * a.tastes[self.problem.musicians[i]] / self.place[i].distance_squared(a.point())
+        };
+
+        (0..self.place.len())
+            .filter(|&i| i != b)
+            .map(|i| {
+                let volume = self.volume(i).unwrap_or(MAX_VOLUME);
+                if volume == 0.0 {
+                    return 0.0;
+                }
+                let after = self.blocks_range_at(i, p);
+                let before = self.blocks_range(i, b);
+                let mut delta = 0.0;
+                for k in after.clone().into_iter().flatten() {
+                    if !in_ranges(&before, k) && self.angles[i][k].nblock == 0 {
+                        delta -= impact(i, k);
+                    }
+                }
+                for k in before.clone().into_iter().flatten() {
+                    if !in_ranges(&after, k) && self.angles[i][k].nblock == 1 {
+                        delta += impact(i, k);
+                    }
+                }
+                self.q[i] * volume * delta
+            })
+            .sum()
+    }
+
+    /// Heap memory which the state holds.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        use std::mem::size_of;
+
+        self.place.capacity() * size_of::<Point>()
+            + self.q.capacity() * size_of::<Score>()
+            + self.angles.capacity() * size_of::<Vec<AttNode>>()
+            + self
+                .angles
+                .iter()
+                .map(|a| a.capacity() * size_of::<AttNode>())
+                .sum::<usize>()
+            + self.scores.capacity() * size_of::<Score>()
+            + self
+                .volumes
+                .as_ref()
+                .map_or(0, |v| v.capacity() * size_of::<Volume>())
+    }
+
+    /// Whether musician `i` can't stand at `p`.
+    pub(crate) fn collides(&self, i: usize, p: Point) -> bool {
+        !self.problem.on_stage(p)
+            || (0..self.place.len())
+                .any(|j| i != j && p.distance_squared(self.place[j]) < MUSICIAN_RADIUS_2 + EPS)
     }
 
     #[allow(dead_code)]
@@ -364,53 +577,428 @@ impl<'a> LocalState<'a> {
     }
 }
 
-#[derive(Copy, Clone, derive_more::Display)]
+/// When to stop annealing.
+#[derive(Clone, Debug, PartialEq)]
 pub enum End {
-    #[display(fmt = "iter-{}", "_0.to_string()")]
     MaxIteration(usize),
-    #[display(fmt = "duration-{}", "_0.as_secs()")]
     MaxDuration(std::time::Duration),
+    /// The best score hasn't improved for this many iterations.
+    NoImprovementIteration(usize),
+    /// The best score hasn't improved for this long.
+    NoImprovementDuration(std::time::Duration),
+    TargetScore(Score),
+    /// Wall-clock time.
+    Deadline(std::time::SystemTime),
+    /// Whichever comes first.
+    Any(Vec<End>),
+}
+
+/// What `End` looks at while a solver runs.
+pub struct Run {
+    niter: usize,
+    timer: std::time::Instant,
+    started: std::time::SystemTime,
+    best: Score,
+    improved_niter: usize,
+    improved_at: std::time::Instant,
+}
+
+impl Run {
+    pub fn new(best: Score) -> Self {
+        Run {
+            niter: 0,
+            timer: std::time::Instant::now(),
+            started: std::time::SystemTime::now(),
+            best,
+            improved_niter: 0,
+            improved_at: std::time::Instant::now(),
+        }
+    }
+
+    pub fn niter(&self) -> usize {
+        self.niter
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.timer.elapsed()
+    }
+
+    pub fn update(&mut self, niter: usize, best: Score) {
+        self.niter = niter;
+        if best > self.best {
+            self.best = best;
+            self.improved_niter = niter;
+            self.improved_at = std::time::Instant::now();
+        }
+    }
+}
+
+impl End {
+    /// How much of the run is done, in [0, 1], for the temperature schedule.
+    /// None if no condition has a fixed budget.
+    pub fn progress(&self, run: &Run) -> Option<f64> {
+        match self {
+            End::MaxIteration(max_iter) => Some(run.niter as f64 / *max_iter as f64),
+            End::MaxDuration(max_duration) => Some(
+                run.timer.elapsed().as_millis() as f64 / max_duration.as_millis().max(1) as f64,
+            ),
+            End::Deadline(deadline) => {
+                let budget = deadline.duration_since(run.started).unwrap_or_default();
+                Some(run.timer.elapsed().as_millis() as f64 / budget.as_millis().max(1) as f64)
+            }
+            End::NoImprovementIteration(_)
+            | End::NoImprovementDuration(_)
+            | End::TargetScore(_) => None,
+            End::Any(ends) => ends
+                .iter()
+                .flat_map(|end| end.progress(run))
+                .map(OrderedFloat)
+                .max()
+                .map(|p| p.0),
+        }
+    }
+
+    pub fn is_done(&self, run: &Run) -> bool {
+        match self {
+            End::MaxIteration(_) | End::MaxDuration(_) | End::Deadline(_) => {
+                self.progress(run).unwrap() >= 1.0
+            }
+            End::NoImprovementIteration(n) => run.niter - run.improved_niter >= *n,
+            End::NoImprovementDuration(duration) => run.improved_at.elapsed() >= *duration,
+            End::TargetScore(target) => run.best >= *target,
+            End::Any(ends) => ends.iter().any(|end| end.is_done(run)),
+        }
+    }
+}
+
+impl std::fmt::Display for End {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            End::MaxIteration(n) => write!(f, "iter-{n}"),
+            End::MaxDuration(duration) => write!(f, "duration-{}", duration.as_secs()),
+            End::NoImprovementIteration(n) => write!(f, "stall-iter-{n}"),
+            End::NoImprovementDuration(duration) => {
+                write!(f, "stall-duration-{}", duration.as_secs())
+            }
+            End::TargetScore(score) => write!(f, "target-{score}"),
+            End::Deadline(deadline) => write!(
+                f,
+                "deadline-{}",
+                deadline
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            ),
+            End::Any(ends) => write!(
+                f,
+                "{}",
+                ends.iter()
+                    .map(|end| end.to_string())
+                    .collect::<Vec<_>>()
+                    .join("+")
+            ),
+        }
+    }
+}
+
+/// The inverse of `Display`, e.g. `duration-3600+stall-iter-1000000`.
+impl std::str::FromStr for End {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        use std::time::Duration;
+
+        // Not the sign of an exponent, e.g. `target-1e+9`.
+        let mut parts = vec![];
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            if c == '+' && !s[..i].ends_with(['e', 'E']) {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+        }
+        if start > 0 {
+            parts.push(&s[start..]);
+            return Ok(End::Any(
+                parts
+                    .into_iter()
+                    .map(|s| s.parse())
+                    .collect::<Result<_>>()?,
+            ));
+        }
+        let parse = |prefix: &str| s.strip_prefix(prefix);
+        Ok(if let Some(n) = parse("stall-iter-") {
+            End::NoImprovementIteration(n.parse()?)
+        } else if let Some(secs) = parse("stall-duration-") {
+            End::NoImprovementDuration(Duration::from_secs(secs.parse()?))
+        } else if let Some(n) = parse("iter-") {
+            End::MaxIteration(n.parse()?)
+        } else if let Some(secs) = parse("duration-") {
+            End::MaxDuration(Duration::from_secs(secs.parse()?))
+        } else if let Some(score) = parse("target-") {
+            End::TargetScore(score.parse()?)
+        } else if let Some(secs) = parse("deadline-") {
+            End::Deadline(std::time::UNIX_EPOCH + Duration::from_secs(secs.parse()?))
+        } else {
+            bail!("invalid end: {s}")
+        })
+    }
+}
+
+// How to revert a cluster move.
+enum Undo {
+    Moves(Vec<(usize, Point)>),
+    Swaps(Vec<(usize, usize)>),
+}
+
+impl Undo {
+    // What the move changed, for the move log.
+    fn changes(&self, st: &LocalState) -> Vec<Change> {
+        match self {
+            Undo::Moves(moves) => moves
+                .iter()
+                .map(|&(i, p0)| Change::Move {
+                    musician: i,
+                    from: p0,
+                    to: st.place[i],
+                })
+                .collect(),
+            // Swapped in order, and each musician once.
+            Undo::Swaps(swaps) => swaps
+                .iter()
+                .map(|&(a, b)| Change::Swap {
+                    a,
+                    b,
+                    from: (st.place[b], st.place[a]),
+                })
+                .collect(),
+        }
+    }
+
+    fn apply(self, st: &mut LocalState) {
+        match self {
+            Undo::Moves(moves) => {
+                for (i, p) in moves.into_iter().rev() {
+                    st.do_move(i, p);
+                }
+            }
+            Undo::Swaps(swaps) => {
+                for (a, b) in swaps.into_iter().rev() {
+                    st.do_swap(a, b);
+                }
+            }
+        }
+    }
 }
 
+fn centroid(st: &LocalState, ids: impl Iterator<Item = usize>) -> Option<Point> {
+    let (n, x, y) = ids.fold((0, 0.0, 0.0), |(n, x, y), i| {
+        (n + 1, x + st.place[i].x, y + st.place[i].y)
+    });
+    (n > 0).then(|| Point::new(x / n as Coord, y / n as Coord))
+}
+
+// Musicians of each instrument.
+fn instrument_groups(problem: &Problem) -> HashMap<Instrument, Vec<usize>> {
+    let mut groups = HashMap::<Instrument, Vec<usize>>::new();
+    for (i, inst) in problem.musicians.iter().enumerate() {
+        groups.entry(*inst).or_default().push(i);
+    }
+    groups
+}
+
+// Moves a musician toward the centroid of the others playing its instrument.
+fn pull_to_centroid(
+    st: &mut LocalState,
+    rng: &mut StdRng,
+    groups: &HashMap<Instrument, Vec<usize>>,
+) -> Option<Undo> {
+    let i = rng.gen_range(0..st.place.len());
+    let group = &groups[&st.problem.musicians[i]];
+    let c = centroid(st, group.iter().copied().filter(|&j| j != i))?;
+    let p0 = st.place[i];
+    let t = rng.gen_range(0.0..1.0);
+    let p = Point::new(p0.x + t * (c.x - p0.x), p0.y + t * (c.y - p0.y));
+    if st.collides(i, p) {
+        return None;
+    }
+    st.do_move(i, p);
+    Some(Undo::Moves(vec![(i, p0)]))
+}
+
+// Translates all musicians of an instrument together.
+fn translate_cluster(
+    st: &mut LocalState,
+    rng: &mut StdRng,
+    groups: &HashMap<Instrument, Vec<usize>>,
+) -> Option<Undo> {
+    let group = &groups[&st.problem.musicians[rng.gen_range(0..st.place.len())]];
+    let dist = 40.0 * rng.gen_range(0.0f64..1.0).powi(2);
+    let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
+    let (dx, dy) = (dist * angle.cos(), dist * angle.sin());
+
+    let in_group = |j: usize| st.problem.musicians[j] == st.problem.musicians[group[0]];
+    let valid = group.iter().all(|&i| {
+        let p = Point::new(st.place[i].x + dx, st.place[i].y + dy);
+        st.problem.on_stage(p)
+            && (0..st.place.len())
+                .all(|j| in_group(j) || p.distance_squared(st.place[j]) >= MUSICIAN_RADIUS_2 + EPS)
+    });
+    if !valid {
+        return None;
+    }
+
+    // Front members first, so that moved members only get farther from the
+    // rest and no intermediate state overlaps. Undo goes in reverse.
+    let mut group = group.clone();
+    group.sort_by_key(|&i| OrderedFloat(-(st.place[i].x * dx + st.place[i].y * dy)));
+    let mut moves = vec![];
+    for i in group {
+        let p0 = st.place[i];
+        st.do_move(i, Point::new(p0.x + dx, p0.y + dy));
+        moves.push((i, p0));
+    }
+    Some(Undo::Moves(moves))
+}
+
+// Swaps the positions of two instrument clusters, pairing the members of
+// each cluster nearest to the other.
+fn swap_clusters(
+    st: &mut LocalState,
+    rng: &mut StdRng,
+    groups: &HashMap<Instrument, Vec<usize>>,
+) -> Option<Undo> {
+    let nm = st.place.len();
+    let (a, b) = (rng.gen_range(0..nm), rng.gen_range(0..nm));
+    if st.problem.musicians[a] == st.problem.musicians[b] {
+        return None;
+    }
+    let mut group_a = groups[&st.problem.musicians[a]].clone();
+    let mut group_b = groups[&st.problem.musicians[b]].clone();
+    let (ca, cb) = (
+        centroid(st, group_a.iter().copied())?,
+        centroid(st, group_b.iter().copied())?,
+    );
+    group_a.sort_by_key(|&i| OrderedFloat(st.place[i].distance_squared(cb)));
+    group_b.sort_by_key(|&i| OrderedFloat(st.place[i].distance_squared(ca)));
+
+    let swaps = group_a.into_iter().zip(group_b).collect::<Vec<_>>();
+    for &(a, b) in &swaps {
+        st.do_swap(a, b);
+    }
+    Some(Undo::Swaps(swaps))
+}
+
+/// Tunable parameters of `run_sa`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Params {
+    /// None scales with the initial score: |score| / sqrt(#musicians).
+    pub temp0: Option<f64>,
+    /// Probability of a volume flip, with volume search.
+    pub volume_prob: f64,
+    /// Probability of a cluster move, on V2.
+    pub cluster_prob: f64,
+    pub swap_prob: f64,
+    // Relative weights of the moves of a single musician.
+    pub jump_weight: f64,
+    pub slide_weight: f64,
+    pub shift_weight: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            temp0: Some(100.0),
+            volume_prob: 0.1,
+            cluster_prob: 0.1,
+            swap_prob: 0.1,
+            jump_weight: 1.0,
+            slide_weight: 1.0,
+            shift_weight: 8.0,
+        }
+    }
+}
+
+impl Params {
+    pub fn validate(&self) -> Result<()> {
+        for p in [self.volume_prob, self.cluster_prob, self.swap_prob] {
+            ensure!((0.0..=1.0).contains(&p), "invalid probability: {p}");
+        }
+        let weights = [self.jump_weight, self.slide_weight, self.shift_weight];
+        ensure!(
+            weights.iter().all(|&w| w >= 0.0) && weights.iter().sum::<f64>() > 0.0,
+            "invalid weights: {weights:?}"
+        );
+        Ok(())
+    }
+
+    fn move_kind(&self, rng: &mut StdRng) -> MoveKind {
+        let r = rng.gen_range(0.0..self.jump_weight + self.slide_weight + self.shift_weight);
+        if r < self.jump_weight {
+            MoveKind::Jump
+        } else if r < self.jump_weight + self.slide_weight {
+            MoveKind::Slide
+        } else {
+            MoveKind::Shift
+        }
+    }
+}
+
+/// `run_sa` rebuilds `LocalState` every this many iterations, which drops
+/// accumulated floating point errors. Tests cross it sooner.
+pub(crate) const REBUILD_INTERVAL: usize = if cfg!(test) { 2_000 } else { 100_000 };
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_sa(
-    name: &str,
+    trace: &mut Trace,
     rng: &mut StdRng,
     problem: &Problem,
     problem_id: ProblemId,
     spec: Spec,
     solution: &Solution,
-    temp0: Option<f64>,
-    end: End,
-) -> Result<(Score, Solution)> {
+    params: &Params,
+    end: &End,
+    volume_search: bool,
+    progress: &Progress,
+) -> Result<(Score, Solution, usize)> {
     let nm = problem.musicians.len();
 
     let mut st = LocalState::new(problem, problem_id, spec, solution);
+    if volume_search {
+        st.set_volumes(solution.volumes.clone());
+    }
 
     let mut sc = st.score;
+    trace.header(&trace::Header {
+        end: end.to_string(),
+    })?;
+    if let Some(log) = trace.move_log() {
+        log.header(&Header {
+            problem_id,
+            problem: problem.clone(),
+            spec,
+            volume_search,
+            rebuild_interval: REBUILD_INTERVAL,
+            score: sc,
+            solution: solution.clone(),
+        })?;
+    }
 
-    let timer = std::time::Instant::now();
+    let mut run = Run::new(sc);
 
-    let temp0 = temp0.unwrap_or_else(|| sc.abs() / (problem.musicians.len() as f64).sqrt());
+    let temp0 = params
+        .temp0
+        .unwrap_or_else(|| sc.abs() / (problem.musicians.len() as f64).sqrt());
     let mut temp = temp0;
 
     let mut best = sc;
     // let mut best_solution = solution.clone();
     let mut best_solution = st.to_solution();
 
-    let mut naccept_positive = 0;
-    let mut naccept_negative = 0;
-    let mut ntotal = 0;
+    let mut moves = Moves::default();
 
-    let mut ncollide = 0;
-    let mut nmove = 0;
-    let sa_plot = project_path(format!("stats/sa/{}/{}.data", name, problem_id));
-    std::fs::create_dir_all(sa_plot.parent().unwrap())?;
-
-    let mut sa_plot = std::fs::File::create(sa_plot)?;
-    writeln!(
-        sa_plot,
-        "iteration score best temperature acceptrate acceptrate_positive acceptrate_negative"
-    )?;
+    let groups = instrument_groups(problem);
 
     let mut niter = 0;
 
@@ -418,45 +1006,102 @@ pub fn run_sa(
         niter += 1;
 
         if niter % 1_000 == 0 {
-            let done = match end {
-                End::MaxIteration(max_iter) => niter as f64 / max_iter as f64,
-                End::MaxDuration(max_duration) => {
-                    timer.elapsed().as_millis() as f64 / max_duration.as_millis() as f64
+            run.update(niter, best);
+            if end.is_done(&run) || interrupted() {
+                info!("end: {end}, niter: {niter}, best: {best:.1}");
+                trace.write(&Record::new(niter, run.elapsed(), sc, best, temp, &moves))?;
+                if let Some(log) = trace.move_log() {
+                    log.flush()?;
                 }
-            };
-            temp = temp0 * (1.0 - done);
-
-            if temp < 0.0 {
-                return Ok((best, best_solution));
+                progress.finish();
+                return Ok((best, best_solution, niter));
             }
+            temp = temp0 * (1.0 - end.progress(&run).unwrap_or(0.0));
+            let (accept_rate, _, _) = moves.accept_rates();
+            progress.update(end, &run, sc, best, temp, accept_rate);
         }
 
         if niter % 10_000 == 0 {
-            let accept_rate =
-                (naccept_positive + naccept_negative) as f64 / 1.0f64.max(ntotal as f64);
-            let accept_rate_positive = naccept_positive as f64 / 1.0f64.max(ntotal as f64);
-            let accept_rate_negative = naccept_negative as f64 / 1.0f64.max(ntotal as f64);
-            info!("temp: {temp:.1}, niter: {niter}, sc: {sc:.1}, best: {best:.1}, ncollide: {ncollide}, nmove: {nmove}, ntotal: {ntotal}, naccept_positive: {naccept_positive}, naccept_negative: {naccept_negative}, accept_rate: {accept_rate:.02}");
-
-            writeln!(
-                sa_plot,
-                "{} {:.1} {:.1} {:.1} {:.3} {:.3} {:.3}",
-                niter, sc, best, temp, accept_rate, accept_rate_positive, accept_rate_negative
-            )?;
-            naccept_positive = 0;
-            naccept_negative = 0;
-            ntotal = 0;
+            let (accept_rate, _, _) = moves.accept_rates();
+            info!("temp: {temp:.1}, niter: {niter}, sc: {sc:.1}, best: {best:.1}, {moves}, accept_rate: {accept_rate:.02}");
+            trace.write(&Record::new(niter, run.elapsed(), sc, best, temp, &moves))?;
+            moves.reset();
         }
 
-        if niter % 100_000 == 0 {
-            st.report_progress().unwrap();
+        if niter % REBUILD_INTERVAL == 0 {
+            if !trace.is_hidden() {
+                st.report_progress()?;
+            }
 
             let solution = st.to_solution();
             st = LocalState::new(problem, problem_id, spec, &solution);
+            if volume_search {
+                st.set_volumes(solution.volumes);
+            }
+        }
+
+        // Flip volume. A silent musician still blocks others.
+        if volume_search && rng.gen_range(0.0..1.0) < params.volume_prob {
+            let id = rng.gen_range(0..nm);
+            let v0 = st.volume(id).unwrap();
+            st.do_volume(id, if v0 == 0.0 { MAX_VOLUME } else { 0.0 });
+
+            let sc2 = st.score;
+            let accepted = sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0);
+            moves.tried(MoveKind::Volume, accepted, sc2 >= sc);
+            if accepted {
+                if let Some(log) = trace.move_log() {
+                    let to = st.volume(id).unwrap();
+                    let change = Change::Volume {
+                        musician: id,
+                        from: v0,
+                        to,
+                    };
+                    log.step(niter, MoveKind::Volume, sc, sc2, vec![change])?;
+                }
+                sc = sc2;
+                if sc > best {
+                    best = sc;
+                    best_solution = st.to_solution();
+                }
+            } else {
+                st.do_volume(id, v0);
+            }
+            continue;
+        }
+
+        // Cluster moves, which V2's closeness factor rewards.
+        if matches!(spec, Spec::V2) && rng.gen_range(0.0..1.0) < params.cluster_prob {
+            let undo = match rng.gen_range(0..3) {
+                0 => pull_to_centroid(&mut st, rng, &groups),
+                1 => translate_cluster(&mut st, rng, &groups),
+                _ => swap_clusters(&mut st, rng, &groups),
+            };
+            let Some(undo) = undo else {
+                moves.collided(MoveKind::Cluster);
+                continue;
+            };
+
+            let sc2 = st.score;
+            let accepted = sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0);
+            moves.tried(MoveKind::Cluster, accepted, sc2 >= sc);
+            if accepted {
+                if let Some(log) = trace.move_log() {
+                    log.step(niter, MoveKind::Cluster, sc, sc2, undo.changes(&st))?;
+                }
+                sc = sc2;
+                if sc > best {
+                    best = sc;
+                    best_solution = st.to_solution();
+                }
+            } else {
+                undo.apply(&mut st);
+            }
+            continue;
         }
 
         // Swap
-        if rng.gen_range(0..10) == 0 {
+        if rng.gen_range(0.0..1.0) < params.swap_prob {
             let a = rng.gen_range(0..nm);
             let b = rng.gen_range(0..nm);
             if a == b {
@@ -466,13 +1111,16 @@ pub fn run_sa(
 
             let sc2 = st.score;
             // let delta = sc2 - sc;
-
-            ntotal += 1;
-            if sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0) {
-                if sc2 >= sc {
-                    naccept_positive += 1;
-                } else {
-                    naccept_negative += 1;
+            let accepted = sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0);
+            moves.tried(MoveKind::Swap, accepted, sc2 >= sc);
+            if accepted {
+                if let Some(log) = trace.move_log() {
+                    let change = Change::Swap {
+                        a,
+                        b,
+                        from: (st.place[b], st.place[a]),
+                    };
+                    log.step(niter, MoveKind::Swap, sc, sc2, vec![change])?;
                 }
                 sc = sc2;
                 if sc > best {
@@ -489,15 +1137,12 @@ pub fn run_sa(
         let id = rng.gen_range(0..nm);
         let p0 = st.place[id];
 
-        let collides = |p: Point| -> bool {
-            !st.problem.on_stage(p)
-                || (0..nm)
-                    .any(|j| id != j && p.distance_squared(st.place[j]) < MUSICIAN_RADIUS_2 + EPS)
-        };
+        let collides = |p: Point| -> bool { st.collides(id, p) };
 
-        let p = match rng.gen_range(0..10) {
-            0 => problem.random_point_on_stage(rng),
-            1 => {
+        let kind = params.move_kind(rng);
+        let p = match kind {
+            MoveKind::Jump => problem.random_point_on_stage(rng),
+            MoveKind::Slide => {
                 let dist = 40.0 * rng.gen_range(0.0f64..1.0).powi(2);
                 let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
 
@@ -521,6 +1166,7 @@ pub fn run_sa(
             // 2 => {
             //   // TODO: Gradient
             // };
+            // Shift
             _ => {
                 let dist = 40.0 * rng.gen_range(0.0f64..1.0).powi(2);
                 let angle = rng.gen_range(0.0f64..2.0 * std::f64::consts::PI);
@@ -529,20 +1175,22 @@ pub fn run_sa(
         };
 
         if collides(p) {
-            ncollide += 1;
+            moves.collided(kind);
             continue;
         }
-        nmove += 1;
         st.do_move(id, p);
 
         let sc2 = st.score;
-        ntotal += 1;
-
-        if sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0) {
-            if sc2 >= sc {
-                naccept_positive += 1;
-            } else {
-                naccept_negative += 1;
+        let accepted = sc2 >= sc || ((sc2 - sc) / temp).exp() > rng.gen_range(0.0..1.0);
+        moves.tried(kind, accepted, sc2 >= sc);
+        if accepted {
+            if let Some(log) = trace.move_log() {
+                let change = Change::Move {
+                    musician: id,
+                    from: p0,
+                    to: p,
+                };
+                log.step(niter, kind, sc, sc2, vec![change])?;
             }
             sc = sc2;
             if sc > best {
@@ -557,14 +1205,23 @@ pub fn run_sa(
 
 pub struct SolverSa {
     problem_id: ProblemId,
+    spec: Spec,
     problem: Problem,
-    temp0: Option<f64>,
+    params: Params,
     end: End,
+    volume_search: bool,
     initial_solution: Solution,
+    // Whether `initial_solution` was given, rather than random.
+    warm_start: bool,
+    progress_bar: Option<ProgressBar>,
+    // Whether to write the trace and drawings of the work in progress.
+    trace: bool,
+    seed: u64,
+    move_log: Option<PathBuf>,
 }
 
 impl SolverSa {
-    fn initial_solution(problem: &Problem) -> Solution {
+    pub(crate) fn initial_solution(problem: &Problem) -> Solution {
         let mut rng = SeedableRng::from_seed([0; 32]);
         let mut placements = vec![];
 
@@ -583,21 +1240,158 @@ impl SolverSa {
         }
     }
 
+    /// Packs each instrument into a hexagonal cluster around the best free
+    /// spot for it, instruments with more impact first.
+    pub fn cluster_initial_solution(problem: &Problem) -> Solution {
+        // Hexagonal lattice slightly sparser than musicians can be.
+        let spacing = MUSICIAN_RADIUS + 1e-3;
+        let mut lattice = vec![];
+        let (minx, miny) = (
+            problem.stage_bottom_left[0] + MUSICIAN_RADIUS,
+            problem.stage_bottom_left[1] + MUSICIAN_RADIUS,
+        );
+        for row in 0.. {
+            let y = miny + row as Coord * spacing * 3.0f64.sqrt() / 2.0;
+            if !problem.on_stage(Point::new(minx, y)) {
+                break;
+            }
+            let offset = if row % 2 == 0 { 0.0 } else { spacing / 2.0 };
+            for col in 0.. {
+                let p = Point::new(minx + offset + col as Coord * spacing, y);
+                if !problem.on_stage(p) {
+                    break;
+                }
+                lattice.push(p);
+            }
+        }
+
+        let value = |inst: Instrument, p: Point| -> Score {
+            problem
+                .attendees
+                .iter()
+                .map(|a| a.tastes[inst] / p.distance_squared(a.point()))
+                .sum()
+        };
+
+        let groups = instrument_groups(problem);
+        let mut order = groups
+            .iter()
+            .map(|(&inst, group)| {
+                let best = lattice
+                    .iter()
+                    .map(|p| OrderedFloat(value(inst, *p)))
+                    .max()
+                    .unwrap_or(OrderedFloat(0.0));
+                (std::cmp::Reverse(best * group.len() as Score), inst)
+            })
+            .collect::<Vec<_>>();
+        order.sort();
+
+        let mut free = vec![true; lattice.len()];
+        let mut placements = vec![None; problem.musicians.len()];
+        for (_, inst) in order {
+            let Some(seed) = (0..lattice.len())
+                .filter(|&k| free[k])
+                .max_by_key(|&k| OrderedFloat(value(inst, lattice[k])))
+            else {
+                break;
+            };
+            let mut nearest = (0..lattice.len()).filter(|&k| free[k]).collect::<Vec<_>>();
+            nearest.sort_by_key(|&k| OrderedFloat(lattice[k].distance_squared(lattice[seed])));
+            for (&i, &k) in groups[&inst].iter().zip(nearest.iter()) {
+                placements[i] = Some(lattice[k]);
+                free[k] = false;
+            }
+        }
+
+        // Not enough room on the lattice. Fall back to random points.
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        for i in 0..placements.len() {
+            while placements[i].is_none() {
+                let p = problem.random_point_on_stage(&mut rng);
+                if placements
+                    .iter()
+                    .flatten()
+                    .all(|q| p.distance_squared(*q) > MUSICIAN_RADIUS_2 + EPS)
+                {
+                    placements[i] = Some(p);
+                }
+            }
+        }
+
+        Solution {
+            placements: placements.into_iter().flatten().collect(),
+            volumes: vec![MAX_VOLUME; problem.musicians.len()],
+        }
+    }
+
     pub fn new(
         problem_id: ProblemId,
         temp0: Option<f64>,
         end: End,
+        volume_search: bool,
         initial_solution: Option<Solution>,
     ) -> Result<Self> {
-        let problem = Problem::new(problem_id)?;
+        Ok(Self::with_problem(
+            problem_id,
+            problem_id.into(),
+            Problem::new(problem_id)?,
+            temp0,
+            end,
+            volume_search,
+            initial_solution,
+        ))
+    }
+
+    /// For a problem which isn't under problem/.
+    pub fn with_problem(
+        problem_id: ProblemId,
+        spec: Spec,
+        problem: Problem,
+        temp0: Option<f64>,
+        end: End,
+        volume_search: bool,
+        initial_solution: Option<Solution>,
+    ) -> Self {
+        let warm_start = initial_solution.is_some();
         let initial_solution = initial_solution.unwrap_or(Self::initial_solution(&problem));
-        Ok(Self {
+        Self {
             problem_id,
+            spec,
             problem,
-            temp0,
+            params: Params {
+                temp0,
+                ..Params::default()
+            },
             end,
+            volume_search,
             initial_solution,
-        })
+            warm_start,
+            progress_bar: None,
+            trace: true,
+            seed: SEED,
+            move_log: None,
+        }
+    }
+
+    /// Writes neither the trace nor drawings of the work in progress.
+    pub fn hide_trace(&mut self) {
+        self.trace = false;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Logs the accepted moves to `path`, for `movelog::replay`.
+    pub fn set_move_log(&mut self, path: PathBuf) {
+        self.move_log = Some(path);
+    }
+
+    pub fn set_params(&mut self, params: Params) -> Result<()> {
+        params.validate()?;
+        self.params = params;
+        Ok(())
     }
 }
 
@@ -607,21 +1401,65 @@ impl Solver for SolverSa {
     }
 
     fn name(&self) -> String {
-        format!("sa-temp0-{:.0}-{}", self.temp0.unwrap_or(0.0), self.end)
+        let tuned = self.params
+            != Params {
+                temp0: self.params.temp0,
+                ..Params::default()
+            };
+        format!(
+            "sa-temp0-{:.0}-{}{}{}",
+            self.params.temp0.unwrap_or(0.0),
+            self.end,
+            if self.volume_search { "-volume" } else { "" },
+            if tuned { "-tuned" } else { "" }
+        )
+    }
+
+    fn set_progress_bar(&mut self, bar: ProgressBar) {
+        self.progress_bar = Some(bar);
+    }
+
+    fn params(&self) -> serde_json::Value {
+        let mut params = serde_json::to_value(&self.params).unwrap();
+        params["end"] = self.end.to_string().into();
+        params["volume_search"] = self.volume_search.into();
+        params
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+
+    fn parent(&self) -> Option<&Solution> {
+        self.warm_start.then_some(&self.initial_solution)
     }
 
     fn solve(&mut self) -> Result<Solved> {
-        let mut rng = SeedableRng::from_seed([0; 32]);
+        let mut rng = seeded_rng(self.seed);
+        let progress = match &self.progress_bar {
+            Some(bar) => Progress::new(bar.clone(), self.problem_id, &self.end),
+            None => Progress::hidden(),
+        };
 
-        let (score, solution) = run_sa(
-            &self.name(),
+        let mut trace = if self.trace {
+            Trace::new(&self.name(), self.problem_id)?
+        } else {
+            Trace::hidden()
+        };
+        if let Some(path) = &self.move_log {
+            trace.set_move_log(MoveLog::create(path)?);
+        }
+        let (score, solution, niter) = run_sa(
+            &mut trace,
             &mut rng,
             &self.problem,
             self.problem_id,
-            self.problem_id.into(),
+            self.spec,
             &self.initial_solution,
-            self.temp0,
-            self.end,
+            &self.params,
+            &self.end,
+            self.volume_search,
+            &progress,
         )?;
         let Solution {
             placements,
@@ -633,6 +1471,8 @@ impl Solver for SolverSa {
             score,
             placements,
             volumes,
+            niter: Some(niter),
+            meta: None,
         })
     }
 }
@@ -646,13 +1486,95 @@ mod tests {
     fn solver_sa() -> Result<()> {
         let cases = [(60, 34597619.50674734)];
         for (id, score) in cases {
-            let mut solver = SolverSa::new(id, Some(100.0), End::MaxIteration(10_000), None)?;
+            let mut solver =
+                SolverSa::new(id, Some(100.0), End::MaxIteration(10_000), false, None)?;
             let solved = solver.solve()?;
             assert_eq!(solved.score, score);
         }
         Ok(())
     }
 
+    #[test]
+    fn sa_volume_search() -> Result<()> {
+        let id = 42;
+        let problem = Problem::new(id)?;
+        let mut rng = SeedableRng::from_seed([0; 32]);
+        let (best, solution, _) = run_sa(
+            &mut Trace::hidden(),
+            &mut rng,
+            &problem,
+            id,
+            id.into(),
+            &SolverSa::initial_solution(&problem),
+            &Params::default(),
+            &End::MaxIteration(20_000),
+            true,
+            &Progress::hidden(),
+        )?;
+        assert_relative_eq!(
+            best,
+            score(&problem, id, id.into(), &solution),
+            max_relative = 1e-6
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cluster_initial_solution_is_valid() -> Result<()> {
+        let id = 60;
+        let problem = Problem::new(id)?;
+        let solution = SolverSa::cluster_initial_solution(&problem);
+        let st = LocalState::new(&problem, id, id.into(), &solution);
+        assert_eq!(solution.placements.len(), problem.musicians.len());
+        assert!((0..solution.placements.len()).all(|i| !st.collides(i, st.place[i])));
+        Ok(())
+    }
+
+    #[test]
+    fn parent_if_warm_started() -> Result<()> {
+        let end = End::MaxIteration(1);
+        let solver = SolverSa::new(42, None, end.clone(), false, None)?;
+        assert!(solver.parent().is_none());
+        let initial = SolverSa::initial_solution(&Problem::new(42)?);
+        let solver = SolverSa::new(42, None, end, false, Some(initial))?;
+        assert!(solver.parent().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn end_parse() -> Result<()> {
+        let end: End = "duration-60+stall-iter-1000+target-1e-5".parse()?;
+        assert_eq!(
+            end,
+            End::Any(vec![
+                End::MaxDuration(std::time::Duration::from_secs(60)),
+                End::NoImprovementIteration(1000),
+                End::TargetScore(1e-5),
+            ])
+        );
+        assert_eq!(
+            end.to_string(),
+            "duration-60+stall-iter-1000+target-0.00001"
+        );
+        assert!("forever-1".parse::<End>().is_err());
+        assert_eq!("target-1e+9".parse::<End>()?, End::TargetScore(1e9));
+        assert_eq!(
+            "target-1E+9+iter-10".parse::<End>()?,
+            End::Any(vec![End::TargetScore(1e9), End::MaxIteration(10)])
+        );
+
+        let end: End = "iter-10000+stall-iter-1000+target-2".parse()?;
+        let mut run = Run::new(0.0);
+        run.update(500, 1.0);
+        assert!(!end.is_done(&run));
+        assert_eq!(end.progress(&run), Some(0.05));
+        run.update(1500, 1.0);
+        assert!(end.is_done(&run));
+        run.update(1500, 2.0);
+        assert!(End::TargetScore(2.0).is_done(&run));
+        Ok(())
+    }
+
     #[test]
     fn sa_score_example_problem() -> Result<()> {
         let problem = Problem::example()?;
@@ -679,6 +1601,8 @@ mod tests {
             3270.0,
             max_relative = 1.0
         );
+        assert_eq!(exact_score(&problem, 0, Spec::V1, &solution), 5343.0);
+        assert_eq!(exact_score(&problem, 0, Spec::V2, &solution), 3270.0);
         Ok(())
     }
 }
@@ -1,14 +1,17 @@
+use crate::meta::{solution_hash, Meta};
 use crate::prelude::*;
 use crate::problem::*;
 use crate::solution::*;
+use crate::solver_sa::Scorer;
 
 use fd_lock::RwLock;
 
 // - problem: {id}.json
 // - solution
-//   - all: {id}-{score}.json
-//   - best: {id}.json
+//   - all: {id}-{solver}-{score}.json, {id}-{solver}-{score}.meta.json
+//   - best: {id}.json, {id}.meta.json
 //   - submission: {id}.json
+//   - history.jsonl
 // - stats
 //   - userboard.json
 //   - For gnuplot:
@@ -32,21 +35,22 @@ impl Userboard {
     pub fn best_score(&self, id: ProblemId) -> Option<Score> {
         assert!(id > 0);
         let index = (id - 1) as usize;
-        self.success.problems[index]
+        self.success.problems.get(index).cloned().flatten()
     }
 }
 
 impl Userboard {
     pub fn new() -> Result<Userboard> {
-        Ok(serde_json::from_str(&read_from("stats/userboard.json")?)?)
+        let s = read_from("stats/userboard.json")
+            .context("no stats/userboard.json. Run userboard first")?;
+        Ok(serde_json::from_str(&s)?)
     }
 
     pub fn total_score(&self) -> Score {
-        self.success.problems.iter().flat_map(|a| a).sum()
+        self.success.problems.iter().flatten().sum()
     }
 
-    #[allow(dead_code)]
-    fn stats(&self) {
+    pub fn stats(&self) {
         println!("# total: {}", self.total_score());
         println!("# id score");
         for (id, score) in self.success.problems.iter().enumerate() {
@@ -59,16 +63,31 @@ impl Userboard {
 
 // After contests
 
+// An empty file is a fresh one.
+fn parse_scores(content: &str) -> Result<HashMap<ProblemId, Score>> {
+    if content.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    serde_json::from_str(content).context("parse best scores")
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BestScore(HashMap<ProblemId, Score>);
 
 impl BestScore {
+    /// Empty if stats/best-score.json doesn't exist yet.
     pub fn new() -> Result<Self> {
-        let f = RwLock::new(
-            std::fs::File::open(project_path("stats/best-score.json")).context("best score new")?,
-        );
+        use std::io::Read as _;
+
+        let path = project_path("stats/best-score.json");
+        if !path.exists() {
+            return Ok(Self(HashMap::new()));
+        }
+        let f = RwLock::new(std::fs::File::open(path).context("best score new")?);
         let f = f.read()?;
-        Ok(Self(serde_json::from_reader(f.deref())?))
+        let mut content = String::new();
+        f.deref().read_to_string(&mut content)?;
+        Ok(Self(parse_scores(&content)?))
     }
 
     pub fn score(&self, id: ProblemId) -> Option<Score> {
@@ -76,57 +95,330 @@ impl BestScore {
     }
 
     pub fn total_score(&self) -> Score {
-        self.0.values().sum()
+        // Not `sum`, which is -0 for no scores.
+        self.0.values().fold(0.0, |total, score| total + score)
     }
 
-    pub fn update(id: ProblemId, new_score: Score) -> Result<()> {
-        use std::io::{Seek as _, SeekFrom};
-
-        // This op should be atomic.
+    // Runs `f` on the scores under the write lock, and saves them if changed.
+    fn with_lock<T>(f: impl FnOnce(&mut HashMap<ProblemId, Score>) -> Result<T>) -> Result<T> {
+        use std::io::{Read as _, Seek as _, SeekFrom};
 
         let path = project_path("stats/best-score.json");
-        let f = std::fs::OpenOptions::new()
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path)
-            .context("best score update")?;
-        let mut f = RwLock::new(f);
-        let mut f = f.write()?;
+            .context("best score lock")?;
+        let mut file = RwLock::new(file);
+        let mut file = file.write()?;
 
-        let mut map: HashMap<ProblemId, Score> =
-            serde_json::from_reader(f.deref()).context("best score update 2")?;
-        map.insert(id, new_score);
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let mut map = parse_scores(&content)?;
+        let old = map.clone();
+        let result = f(&mut map)?;
+        if map == old {
+            return Ok(result);
+        }
 
         // Truncate
-        f.set_len(0)?;
-        f.seek(SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
 
         let json = serde_json::to_string(&map)?;
-        write!(f, "{}", json)?;
-        Ok(())
+        write!(file, "{}", json)?;
+        Ok(result)
+    }
+
+    pub fn update(id: ProblemId, new_score: Score) -> Result<()> {
+        BestScore::with_lock(|map| {
+            map.insert(id, new_score);
+            Ok(())
+        })
+    }
+
+    /// Saves `solution` as solution/best/{id}.json if `score` beats the best
+    /// score, comparing and writing under one lock. Returns the previous best
+    /// score and whether `solution` replaced it.
+    pub fn promote(
+        id: ProblemId,
+        score: Score,
+        solution: &Solution,
+        meta: Option<&Meta>,
+    ) -> Result<(Option<Score>, bool)> {
+        BestScore::with_lock(|map| {
+            let previous = map.get(&id).cloned();
+            if previous.is_some_and(|best| best >= score) {
+                return Ok((previous, false));
+            }
+            let path = format!("solution/best/{id}.json");
+            // The sidecar first, so that a stale one never describes the new
+            // solution.
+            match meta {
+                Some(meta) => {
+                    write_atomic(Meta::path(&path), &serde_json::to_string_pretty(meta)?)?
+                }
+                None => {
+                    let _ = std::fs::remove_file(project_path(Meta::path(&path)));
+                }
+            }
+            write_atomic(&path, &serde_json::to_string(solution)?)?;
+            map.insert(id, score);
+            Ok((previous, true))
+        })
     }
 
-    pub fn refresh() -> Result<()> {
+    /// Problems whose best score, best solution and its sidecar disagree.
+    pub fn check() -> Result<Vec<(ProblemId, String)>> {
+        let best_score = BestScore::new()?;
+        let mut issues = vec![];
+        for id in problem_ids() {
+            let stored = best_score.score(id);
+            let solution = match Solution::best(id) {
+                Ok(solution) => solution,
+                Err(_) => {
+                    if let Some(stored) = stored {
+                        issues.push((id, format!("no solution for score {stored}")));
+                    }
+                    continue;
+                }
+            };
+            let problem = Problem::new(id)?;
+            if let Err(e) = solution.validate(&problem) {
+                issues.push((id, format!("invalid solution: {e}")));
+                continue;
+            }
+            // Scores are fast ones unless refreshed with the exact scorer.
+            let score = Scorer::Fast.score(&problem, id, id.into(), &solution);
+            match stored {
+                None => issues.push((id, format!("no score for solution of {score}"))),
+                Some(stored)
+                    if relative_ne!(stored, score, max_relative = 1e-9)
+                        && stored != Scorer::Exact.score(&problem, id, id.into(), &solution) =>
+                {
+                    issues.push((id, format!("score {stored} != solution score {score}")))
+                }
+                Some(_) => {}
+            }
+            if let Ok(meta) = Meta::read(&format!("solution/best/{id}.json")) {
+                if meta.solution_hash != solution_hash(&solution) {
+                    issues.push((id, "metadata describes another solution".to_string()));
+                }
+            }
+        }
+        Ok(issues)
+    }
+
+    /// Rescores solution/best with `scorer` and prints what changed. Scores
+    /// of best solutions which are missing, unreadable or invalid are dropped.
+    /// Note that solvers compare their fast scores with the stored ones.
+    pub fn refresh(scorer: Scorer, dry_run: bool) -> Result<()> {
+        if dry_run {
+            BestScore::rescore(scorer, &BestScore::new()?.0)?;
+            println!("# dry run: stats/best-score.json is not written");
+            return Ok(());
+        }
+        // Under the lock, so that `promote` can't change solution/best in
+        // between.
+        BestScore::with_lock(|scores| {
+            *scores = BestScore::rescore(scorer, scores)?;
+            Ok(())
+        })
+    }
+
+    // Rescores solution/best with `scorer`, printing the diff from `old`.
+    fn rescore(
+        scorer: Scorer,
+        old: &HashMap<ProblemId, Score>,
+    ) -> Result<HashMap<ProblemId, Score>> {
+        use rayon::prelude::*;
+
+        let mut rescored = problem_ids()
+            .into_par_iter()
+            .map(|id| {
+                let rescore = || -> Result<Option<Score>> {
+                    if !project_path(format!("solution/best/{id}.json")).exists() {
+                        return Ok(None);
+                    }
+                    let problem = Problem::new(id)?;
+                    let solution = Solution::best(id)?;
+                    solution.validate(&problem)?;
+                    Ok(Some(scorer.score(&problem, id, id.into(), &solution)))
+                };
+                (id, rescore())
+            })
+            .collect::<HashMap<_, _>>();
+
+        let ids = rescored
+            .keys()
+            .chain(old.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
         let mut map = HashMap::new();
-        for id in 1..=90 {
-            if let Ok(best_solution) = Solution::best(id) {
-                let problem = Problem::new(id)?;
-                let score = crate::solver_sa::score(&problem, id, id.into(), &best_solution);
-                map.insert(id, score);
+        let mut nchanged = 0;
+        println!("# id old new diff");
+        for id in ids {
+            let old = old.get(&id).cloned();
+            let new = match rescored.remove(&id) {
+                Some(Ok(new)) => new,
+                Some(Err(e)) => {
+                    println!("# {id}: {e:#}");
+                    None
+                }
+                // Can't rescore without the problem.
+                None if project_path(format!("solution/best/{id}.json")).exists() => {
+                    println!("# {id}: problem/{id}.json is missing. Keeping the old score");
+                    old
+                }
+                None => None,
+            };
+            if let Some(new) = new {
+                map.insert(id, new);
+            }
+            let fmt = |score: Option<Score>| score.map_or("-".to_string(), |s| s.to_string());
+            let changed = match (old, new) {
+                (Some(old), Some(new)) => relative_ne!(old, new, max_relative = 1e-9),
+                (old, new) => old.is_some() != new.is_some(),
+            };
+            if changed {
+                nchanged += 1;
+                let diff = match (old, new) {
+                    (Some(old), Some(new)) => format!("{:+}", new - old),
+                    _ => "-".to_string(),
+                };
+                println!("{id} {} {} {diff}", fmt(old), fmt(new));
             }
         }
+        let total = |scores: &HashMap<ProblemId, Score>| {
+            scores.values().fold(0.0, |total, score| total + score)
+        };
+        println!(
+            "# scorer: {scorer}, changed: {nchanged}, total: {} -> {}",
+            total(old),
+            total(&map)
+        );
+        Ok(map)
+    }
+}
 
-        let path = project_path("stats/best-score.json");
-        let f = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(path)
-            .context("best score refresh")?;
-        let mut f = RwLock::new(f);
-        let mut f = f.write()?;
-        let json = serde_json::to_string(&map)?;
-        write!(f, "{}", json)?;
+/// The userboard against the local best, per problem. Scores on the
+/// userboard are the judge's, and local ones are fast ones unless refreshed
+/// with the exact scorer.
+pub struct Comparison {
+    pub id: ProblemId,
+    pub submitted: Option<Score>,
+    pub best: Option<Score>,
+    /// Whether solution/best/{id}.json differs from the last submitted one,
+    /// that is, whether submit-best would submit it.
+    pub to_submit: bool,
+}
+
+impl Comparison {
+    pub fn all() -> Result<Vec<Comparison>> {
+        let userboard = Userboard::new()?;
+        let best_score = BestScore::new()?;
+        let ids = problem_ids()
+            .into_iter()
+            .chain(best_score.0.keys().cloned())
+            .collect::<BTreeSet<_>>();
+        Ok(ids
+            .into_iter()
+            .map(|id| {
+                let best = read_from(format!("solution/best/{id}.json")).ok();
+                let submission = read_from(format!("solution/submission/{id}.json")).ok();
+                Comparison {
+                    id,
+                    submitted: userboard.best_score(id),
+                    best: best_score.score(id),
+                    to_submit: best.is_some() && best != submission,
+                }
+            })
+            .collect())
+    }
+
+    pub fn delta(&self) -> Option<Score> {
+        Some(self.best? - self.submitted.unwrap_or(0.0))
+    }
+
+    pub fn stats() -> Result<()> {
+        let comparisons = Comparison::all()?;
+        let fmt = |score: Option<Score>| score.map_or("-".to_string(), |s| format!("{s:.0}"));
+        println!("# id submitted best delta submit");
+        for c in &comparisons {
+            println!(
+                "{} {} {} {} {}",
+                c.id,
+                fmt(c.submitted),
+                fmt(c.best),
+                c.delta().map_or("-".to_string(), |d| format!("{d:+.0}")),
+                if c.to_submit { "yes" } else { "no" }
+            );
+        }
+        let to_submit = comparisons
+            .iter()
+            .filter(|c| c.to_submit)
+            .map(|c| c.id.to_string())
+            .collect::<Vec<_>>();
+        println!(
+            "# submitted: {:.0}, best: {:.0}, to submit: {} [{}]",
+            comparisons.iter().flat_map(|c| c.submitted).sum::<Score>(),
+            comparisons.iter().flat_map(|c| c.best).sum::<Score>(),
+            to_submit.len(),
+            to_submit.join(" ")
+        );
+        Ok(())
+    }
+}
+
+pub struct UpperBound {
+    pub id: ProblemId,
+    pub best: Option<Score>,
+    pub bound: Score,
+}
+
+impl UpperBound {
+    pub fn new(id: ProblemId, best_score: &BestScore) -> Result<UpperBound> {
+        let problem = Problem::new(id)?;
+        Ok(UpperBound {
+            id,
+            best: best_score.score(id),
+            // Best scores are exact ones after `refresh --scorer exact`.
+            bound: problem.exact_upper_bound(id.into()),
+        })
+    }
+
+    /// Gap between the best score and the bound, in percent of the bound.
+    pub fn gap(&self) -> Option<Score> {
+        self.best
+            .map(|best| (self.bound - best) / self.bound.abs().max(1.0) * 100.0)
+    }
+
+    pub fn all() -> Result<Vec<UpperBound>> {
+        use rayon::prelude::*;
+        let best_score = BestScore::new()?;
+        problem_ids()
+            .into_par_iter()
+            .map(|id| UpperBound::new(id, &best_score))
+            .collect()
+    }
+
+    pub fn stats() -> Result<()> {
+        let bounds = UpperBound::all()?;
+        println!("# id best bound gap(%)");
+        for b in &bounds {
+            let best = b.best.map_or("-".to_string(), |best| format!("{best:.0}"));
+            let gap = b.gap().map_or("-".to_string(), |gap| format!("{gap:.2}"));
+            println!("{} {best} {:.0} {gap}", b.id, b.bound);
+        }
+        let best = bounds.iter().flat_map(|b| b.best).sum::<Score>();
+        let bound = bounds.iter().map(|b| b.bound).sum::<Score>();
+        println!(
+            "# total: best: {best:.0}, bound: {bound:.0}, gap: {:.2}%",
+            (bound - best) / bound.abs().max(1.0) * 100.0
+        );
         Ok(())
     }
 }
@@ -0,0 +1,445 @@
+use crate::prelude::*;
+
+use crate::meta;
+use crate::movelog::MoveLog;
+use crate::problem::ProblemId;
+
+// Traces of SA runs: stats/sa/{solver name}/{run id}/{problem id}.jsonl, a
+// `Header`, then one `Record` per line.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveKind {
+    Volume,
+    Cluster,
+    Swap,
+    // To a random point on the stage.
+    Jump,
+    // As far as possible toward a random direction.
+    Slide,
+    // To a random point nearby.
+    Shift,
+}
+
+impl MoveKind {
+    pub const ALL: [MoveKind; 6] = [
+        MoveKind::Volume,
+        MoveKind::Cluster,
+        MoveKind::Swap,
+        MoveKind::Jump,
+        MoveKind::Slide,
+        MoveKind::Shift,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveKind::Volume => "volume",
+            MoveKind::Cluster => "cluster",
+            MoveKind::Swap => "swap",
+            MoveKind::Jump => "jump",
+            MoveKind::Slide => "slide",
+            MoveKind::Shift => "shift",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MoveStats {
+    pub tried: usize,
+    /// Moves which were not tried because they collide.
+    pub collided: usize,
+    pub accepted_positive: usize,
+    pub accepted_negative: usize,
+}
+
+/// Move statistics since the last `reset`.
+#[derive(Default)]
+pub struct Moves([MoveStats; MoveKind::ALL.len()]);
+
+impl Moves {
+    pub fn collided(&mut self, kind: MoveKind) {
+        self.0[kind as usize].collided += 1;
+    }
+
+    /// `positive`: the move didn't decrease the score.
+    pub fn tried(&mut self, kind: MoveKind, accepted: bool, positive: bool) {
+        let stats = &mut self.0[kind as usize];
+        stats.tried += 1;
+        if accepted {
+            if positive {
+                stats.accepted_positive += 1;
+            } else {
+                stats.accepted_negative += 1;
+            }
+        }
+    }
+
+    fn total(&self) -> MoveStats {
+        self.0
+            .iter()
+            .fold(MoveStats::default(), |total, s| MoveStats {
+                tried: total.tried + s.tried,
+                collided: total.collided + s.collided,
+                accepted_positive: total.accepted_positive + s.accepted_positive,
+                accepted_negative: total.accepted_negative + s.accepted_negative,
+            })
+    }
+
+    /// Acceptance rates of all, positive and negative moves.
+    pub fn accept_rates(&self) -> (f64, f64, f64) {
+        let total = self.total();
+        let tried = 1.0f64.max(total.tried as f64);
+        (
+            (total.accepted_positive + total.accepted_negative) as f64 / tried,
+            total.accepted_positive as f64 / tried,
+            total.accepted_negative as f64 / tried,
+        )
+    }
+
+    pub fn reset(&mut self) {
+        *self = Moves::default();
+    }
+}
+
+impl std::fmt::Display for Moves {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.total();
+        write!(
+            f,
+            "ncollide: {}, ntotal: {}, naccept_positive: {}, naccept_negative: {}",
+            total.collided, total.tried, total.accepted_positive, total.accepted_negative
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Header {
+    /// When the run stops, e.g. `duration-60`.
+    pub end: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    pub iteration: usize,
+    pub elapsed_secs: f64,
+    pub score: Score,
+    pub best: Score,
+    pub temperature: f64,
+    pub accept_rate: f64,
+    pub accept_rate_positive: f64,
+    pub accept_rate_negative: f64,
+    /// Since the previous record.
+    pub moves: BTreeMap<String, MoveStats>,
+}
+
+impl Record {
+    pub fn new(
+        iteration: usize,
+        elapsed: std::time::Duration,
+        score: Score,
+        best: Score,
+        temperature: f64,
+        moves: &Moves,
+    ) -> Self {
+        let (accept_rate, accept_rate_positive, accept_rate_negative) = moves.accept_rates();
+        Record {
+            iteration,
+            elapsed_secs: elapsed.as_secs_f64(),
+            score,
+            best,
+            temperature,
+            accept_rate,
+            accept_rate_positive,
+            accept_rate_negative,
+            moves: MoveKind::ALL
+                .iter()
+                .map(|&kind| (kind.name().to_string(), moves.0[kind as usize]))
+                .collect(),
+        }
+    }
+}
+
+/// Identifies the runs of this process, e.g. `20230710T120000-1234`.
+pub fn run_id() -> &'static str {
+    static RUN_ID: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    RUN_ID.get_or_init(|| {
+        let time = meta::format_timestamp(meta::timestamp()).replace(['-', ':', 'Z'], "");
+        format!("{time}-{}", std::process::id())
+    })
+}
+
+pub struct Trace {
+    file: Option<std::io::BufWriter<std::fs::File>>,
+    move_log: Option<MoveLog>,
+}
+
+impl Trace {
+    /// Writes nothing. `run_sa` doesn't draw the work in progress either.
+    pub fn hidden() -> Self {
+        Trace {
+            file: None,
+            move_log: None,
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.file.is_none()
+    }
+
+    pub fn new(name: &str, problem_id: ProblemId) -> Result<Self> {
+        let path = project_path(format!("stats/sa/{name}/{}/{problem_id}.jsonl", run_id()));
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        info!("trace: {}", path.display());
+        Ok(Trace {
+            file: Some(std::io::BufWriter::new(std::fs::File::create(path)?)),
+            move_log: None,
+        })
+    }
+
+    /// Also logs the accepted moves, even if hidden.
+    pub fn set_move_log(&mut self, move_log: MoveLog) {
+        self.move_log = Some(move_log);
+    }
+
+    pub fn move_log(&mut self) -> Option<&mut MoveLog> {
+        self.move_log.as_mut()
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}", serde_json::to_string(value)?)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn header(&mut self, header: &Header) -> Result<()> {
+        self.write_line(header)
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        self.write_line(record)
+    }
+}
+
+pub fn read(path: impl AsRef<Path>) -> Result<(Header, Vec<Record>)> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("read {path:?}"))?;
+    let mut lines = s.lines().filter(|line| !line.trim().is_empty());
+    let header = serde_json::from_str(lines.next().context("empty trace")?)
+        .with_context(|| format!("header of {path:?}"))?;
+    let records = lines
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect::<Result<_>>()?;
+    Ok((header, records))
+}
+
+/// Writes the columns which stats/sa.gnuplot plots.
+pub fn write_data(header: &Header, records: &[Record], out_path: impl AsRef<Path>) -> Result<()> {
+    let mut data = format!(
+        "# end: {}\niteration score best temperature acceptrate acceptrate_positive acceptrate_negative\n",
+        header.end
+    );
+    for r in records {
+        data += &format!(
+            "{} {:.1} {:.1} {:.1} {:.3} {:.3} {:.3}\n",
+            r.iteration,
+            r.score,
+            r.best,
+            r.temperature,
+            r.accept_rate,
+            r.accept_rate_positive,
+            r.accept_rate_negative
+        );
+    }
+    let out_path = out_path.as_ref();
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(out_path, data)?;
+    Ok(())
+}
+
+// Chart layout.
+const WIDTH: f64 = 800.0;
+const PANEL_HEIGHT: f64 = 250.0;
+const MARGIN: f64 = 70.0;
+
+// Name, color, and value.
+type Curve = (&'static str, &'static str, fn(&Record) -> f64);
+
+// One panel of `plot`: curves against the iteration which share a y-axis.
+fn panel(records: &[Record], top: f64, title: &str, curves: &[Curve]) -> svg::node::element::Group {
+    use svg::node::element::*;
+    use svg::node::Text as TextNode;
+
+    let x_max = records.last().map_or(1, |r| r.iteration).max(1) as f64;
+    let values = || curves.iter().flat_map(|(_, _, f)| records.iter().map(f));
+    let y_min = values().fold(f64::INFINITY, f64::min);
+    let y_max = values().fold(f64::NEG_INFINITY, f64::max);
+    let (y_min, y_max) = if y_min < y_max {
+        (y_min, y_max)
+    } else {
+        (y_min - 1.0, y_min + 1.0)
+    };
+    let plot_width = WIDTH - 2.0 * MARGIN;
+    let x = |iteration: usize| MARGIN + iteration as f64 / x_max * plot_width;
+    let y = |v: f64| top + PANEL_HEIGHT - (v - y_min) / (y_max - y_min) * PANEL_HEIGHT;
+
+    let text = |x: f64, y: f64, anchor: &str, s: String| {
+        Text::new()
+            .set("x", x)
+            .set("y", y)
+            .set("font-size", 12)
+            .set("text-anchor", anchor)
+            .add(TextNode::new(s))
+    };
+
+    let mut group = Group::new()
+        .add(
+            Rectangle::new()
+                .set("x", MARGIN)
+                .set("y", top)
+                .set("width", plot_width)
+                .set("height", PANEL_HEIGHT)
+                .set("style", "fill:none;stroke:gray"),
+        )
+        .add(text(MARGIN, top - 8.0, "start", title.to_string()))
+        .add(text(
+            MARGIN - 4.0,
+            top + 12.0,
+            "end",
+            format!("{y_max:.3e}"),
+        ))
+        .add(text(
+            MARGIN - 4.0,
+            top + PANEL_HEIGHT,
+            "end",
+            format!("{y_min:.3e}"),
+        ))
+        .add(text(
+            WIDTH - MARGIN,
+            top + PANEL_HEIGHT + 16.0,
+            "end",
+            format!("{x_max}"),
+        ));
+
+    for (k, (name, color, f)) in curves.iter().enumerate() {
+        let points = records
+            .iter()
+            .map(|r| format!("{:.1},{:.1}", x(r.iteration), y(f(r))))
+            .collect::<Vec<_>>()
+            .join(" ");
+        group = group
+            .add(Polyline::new().set("points", points).set(
+                "style",
+                format!("fill:none;stroke:{color};stroke-width:1.5"),
+            ))
+            .add(
+                text(
+                    WIDTH - MARGIN - 100.0 * k as f64,
+                    top - 8.0,
+                    "end",
+                    name.to_string(),
+                )
+                .set("fill", *color),
+            );
+    }
+    group
+}
+
+/// Draws the score and the temperature against the iteration.
+pub fn plot(header: &Header, records: &[Record], out_path: impl AsRef<Path>) -> Result<()> {
+    use svg::node::element::*;
+
+    ensure!(!records.is_empty(), "empty trace");
+    let document = svg::Document::new()
+        .set("viewBox", (0, 0, WIDTH, 2.0 * PANEL_HEIGHT + 3.0 * MARGIN))
+        .add(
+            Rectangle::new()
+                .set("width", "100%")
+                .set("height", "100%")
+                .set("fill", "white"),
+        )
+        .add(panel(
+            records,
+            MARGIN,
+            &format!("score (end: {})", header.end),
+            &[
+                ("score", "steelblue", |r| r.score),
+                ("best", "crimson", |r| r.best),
+            ],
+        ))
+        .add(panel(
+            records,
+            2.0 * MARGIN + PANEL_HEIGHT,
+            "temperature",
+            &[("temperature", "darkorange", |r| r.temperature)],
+        ));
+
+    let out_path = out_path.as_ref();
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    svg::save(out_path, &document)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_plot() -> Result<()> {
+        let mut moves = Moves::default();
+        moves.tried(MoveKind::Swap, true, true);
+        moves.tried(MoveKind::Shift, true, false);
+        moves.tried(MoveKind::Shift, false, false);
+        moves.collided(MoveKind::Jump);
+        let (accept_rate, positive, negative) = moves.accept_rates();
+        assert_relative_eq!(accept_rate, 2.0 / 3.0);
+        assert_relative_eq!(positive, 1.0 / 3.0);
+        assert_relative_eq!(negative, 1.0 / 3.0);
+
+        let records = (1..=3)
+            .map(|i| {
+                Record::new(
+                    i * 10_000,
+                    std::time::Duration::from_secs(i as u64),
+                    i as f64,
+                    i as f64,
+                    100.0 / i as f64,
+                    &moves,
+                )
+            })
+            .collect::<Vec<_>>();
+        let dir = std::env::temp_dir().join(format!("trace-{}", std::process::id()));
+        let mut trace = Trace {
+            file: Some(std::io::BufWriter::new(std::fs::File::create(
+                dir.with_extension("jsonl"),
+            )?)),
+            move_log: None,
+        };
+        trace.header(&Header {
+            end: "iter-30000".to_string(),
+        })?;
+        for r in &records {
+            trace.write(r)?;
+        }
+        let (header, read) = read(dir.with_extension("jsonl"))?;
+        assert_eq!(header.end, "iter-30000");
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[2].moves["shift"].accepted_negative, 1);
+        assert_eq!(read[2].moves["jump"].collided, 1);
+
+        plot(&header, &read, dir.join("trace.svg"))?;
+        assert!(dir.join("trace.svg").exists());
+        write_data(&header, &read, dir.join("trace.data"))?;
+        let data = std::fs::read_to_string(dir.join("trace.data"))?;
+        assert_eq!(data.lines().next(), Some("# end: iter-30000"));
+        assert_eq!(data.lines().count(), 5);
+        std::fs::remove_file(dir.with_extension("jsonl"))?;
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use crate::prelude::*;
+
+use crate::problem::*;
+use crate::solution::*;
+
+// Maps a solution of one problem onto another to warm-start SA.
+
+// Maps `p` from the placeable area of `source` onto that of `target`.
+fn map_point(source: &Problem, target: &Problem, p: Point) -> Point {
+    let axis = |v: Coord, src_min: Coord, src_len: Coord, dst_min: Coord, dst_len: Coord| {
+        let src_len = src_len - 2.0 * MUSICIAN_RADIUS;
+        let dst_len = dst_len - 2.0 * MUSICIAN_RADIUS;
+        let t = if src_len > 0.0 {
+            (v - src_min - MUSICIAN_RADIUS) / src_len
+        } else {
+            0.5
+        };
+        dst_min + MUSICIAN_RADIUS + t.clamp(0.0, 1.0) * dst_len
+    };
+    Point::new(
+        axis(
+            p.x,
+            source.stage_bottom_left[0],
+            source.stage_width,
+            target.stage_bottom_left[0],
+            target.stage_width,
+        ),
+        axis(
+            p.y,
+            source.stage_bottom_left[1],
+            source.stage_height,
+            target.stage_bottom_left[1],
+            target.stage_height,
+        ),
+    )
+}
+
+fn is_free(problem: &Problem, placed: &[Point], p: Point) -> bool {
+    problem.on_stage(p)
+        && placed
+            .iter()
+            .all(|q| p.distance_squared(*q) >= MUSICIAN_RADIUS_2 + EPS)
+}
+
+// The nearest free point around `p`, searching rings of growing radius.
+fn repair(problem: &Problem, placed: &[Point], p: Point, rng: &mut StdRng) -> Point {
+    if is_free(problem, placed, p) {
+        return p;
+    }
+    for ring in 1..=100 {
+        let r = ring as Coord * MUSICIAN_RADIUS / 2.0;
+        let n = 6 * ring;
+        let offset = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+        for k in 0..n {
+            let angle = offset + 2.0 * std::f64::consts::PI * k as f64 / n as f64;
+            let q = Point::new(p.x + r * angle.cos(), p.y + r * angle.sin());
+            if is_free(problem, placed, q) {
+                return q;
+            }
+        }
+    }
+    loop {
+        let q = problem.random_point_on_stage(rng);
+        if is_free(problem, placed, q) {
+            return q;
+        }
+    }
+}
+
+/// Maps `solution` of `source` onto `target`: placements are scaled into the
+/// target stage, moved apart where they collide, and then each slot goes to
+/// the instrument which gains the most there.
+pub fn transfer(source: &Problem, solution: &Solution, target: &Problem) -> Solution {
+    let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+    let nm = target.musicians.len();
+
+    let mut slots: Vec<Point> = vec![];
+    for p in solution.placements.iter().take(nm) {
+        let p = repair(target, &slots, map_point(source, target, *p), &mut rng);
+        slots.push(p);
+    }
+    while slots.len() < nm {
+        let p = repair(
+            target,
+            &slots,
+            target.random_point_on_stage(&mut rng),
+            &mut rng,
+        );
+        slots.push(p);
+    }
+
+    let inst_cnt = inst_cnt(&target.musicians);
+    let mut pairs = inst_cnt
+        .keys()
+        .flat_map(|&inst| {
+            slots.iter().enumerate().map(move |(slot, p)| {
+                let value = target
+                    .attendees
+                    .iter()
+                    .map(|a| a.tastes[inst] / p.distance_squared(a.point()))
+                    .sum::<Score>();
+                (OrderedFloat(value), inst, slot)
+            })
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by_key(|(value, _, _)| std::cmp::Reverse(*value));
+
+    let mut slots_of = HashMap::<Instrument, Vec<usize>>::new();
+    let mut used = vec![false; nm];
+    for (_, inst, slot) in pairs {
+        let taken = slots_of.entry(inst).or_default();
+        if !used[slot] && taken.len() < inst_cnt[&inst] {
+            used[slot] = true;
+            taken.push(slot);
+        }
+    }
+
+    let placements = target
+        .musicians
+        .iter()
+        .map(|inst| slots[slots_of.get_mut(inst).unwrap().pop().unwrap()])
+        .collect();
+    Solution {
+        placements,
+        volumes: vec![MAX_VOLUME; nm],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_is_valid() -> Result<()> {
+        let (source_id, target_id) = (42, 45);
+        let source = Problem::new(source_id)?;
+        let target = Problem::new(target_id)?;
+        let solution = transfer(&source, &Solution::best(source_id)?, &target);
+        assert_eq!(solution.placements.len(), target.musicians.len());
+        for (i, p) in solution.placements.iter().enumerate() {
+            assert!(is_free(&target, &solution.placements[..i], *p));
+        }
+        Ok(())
+    }
+}
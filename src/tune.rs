@@ -0,0 +1,230 @@
+use crate::prelude::*;
+
+use crate::experiment::SolverSpec;
+use crate::problem::*;
+use crate::solver::Solver;
+use crate::solver_sa::{End, Params, SolverSa};
+
+use serde_json::Value;
+
+// Tunes `Params` of SA per problem class by racing. Candidates, a grid as in
+// experiments, run on one instance, a problem and a seed, after another. After
+// each instance, a one-sided paired t-test drops the candidates which are
+// significantly worse than the leader. The winners go to stats/tuned.json,
+// which `solve --tuned` reads.
+//
+// {
+//   "problems": [42, 60, 85],
+//   "seeds": [0, 1, 2],
+//   "end": "iter-100000",
+//   "grid": {"temp0": [10, 100, null], "swap_prob": [0.05, 0.1, 0.2]}
+// }
+
+pub const TUNED: &str = "stats/tuned.json";
+
+// Instances before the first elimination.
+const MIN_INSTANCES: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Race {
+    pub problems: Vec<ProblemId>,
+    pub seeds: Vec<u64>,
+    pub end: String,
+    #[serde(default)]
+    pub volume_search: bool,
+    pub grid: BTreeMap<String, Vec<Value>>,
+}
+
+impl Race {
+    pub fn candidates(&self) -> Result<Vec<Params>> {
+        SolverSpec {
+            solver: "sa".to_string(),
+            grid: self.grid.clone(),
+        }
+        .configs()
+        .into_iter()
+        .map(|config| {
+            let params: Params = serde_json::from_value(Value::Object(config.params.clone()))
+                .with_context(|| format!("candidate: {config}"))?;
+            params.validate()?;
+            Ok(params)
+        })
+        .collect()
+    }
+}
+
+/// Tuned parameters per class, e.g. "V2-large".
+pub fn read_tuned() -> Result<BTreeMap<String, Params>> {
+    match read_from(TUNED) {
+        Ok(s) => serde_json::from_str(&s).with_context(|| format!("parse {TUNED}")),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// The tuned parameters for `class`, if any.
+pub fn tuned(class: Class) -> Result<Option<Params>> {
+    Ok(read_tuned()?.remove(&class.to_string()))
+}
+
+// One-sided critical values of Student's t at 5%, for 1 to 30 degrees of
+// freedom.
+const T_CRITICAL: [f64; 30] = [
+    6.314, 2.920, 2.353, 2.132, 2.015, 1.943, 1.895, 1.860, 1.833, 1.812, 1.796, 1.782, 1.771,
+    1.761, 1.753, 1.746, 1.740, 1.734, 1.729, 1.725, 1.721, 1.717, 1.714, 1.711, 1.708, 1.706,
+    1.703, 1.701, 1.699, 1.697,
+];
+
+fn t_critical(df: usize) -> f64 {
+    T_CRITICAL.get(df.wrapping_sub(1)).cloned().unwrap_or(1.645)
+}
+
+// Mean relative differences smaller than this are ties, whatever the variance.
+pub(crate) const TIE: f64 = 1e-9;
+
+/// Whether `a` is significantly lower than `b`, paired.
+pub fn worse(a: &[f64], b: &[f64]) -> bool {
+    let n = a.len();
+    if n < 2 {
+        return false;
+    }
+    let d = a.iter().zip(b).map(|(a, b)| a - b).collect::<Vec<_>>();
+    let mean = d.iter().sum::<f64>() / n as f64;
+    if mean > -TIE {
+        return false;
+    }
+    let var = d.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+    if var == 0.0 {
+        return true;
+    }
+    mean / (var / n as f64).sqrt() < -t_critical(n - 1)
+}
+
+fn run(race: &Race, params: &Params, id: ProblemId, seed: u64) -> Result<Score> {
+    let mut solver = SolverSa::new(
+        id,
+        params.temp0,
+        race.end.parse::<End>()?,
+        race.volume_search,
+        None,
+    )?;
+    solver.set_params(params.clone())?;
+    solver.hide_trace();
+    solver.set_seed(seed);
+    Ok(solver.solve()?.score)
+}
+
+/// Races `candidates` on `instances`. Returns the index of the winner.
+fn race_class(race: &Race, candidates: &[Params], instances: &[(ProblemId, u64)]) -> Result<usize> {
+    use rayon::prelude::*;
+
+    let mut alive = (0..candidates.len()).collect::<Vec<_>>();
+    // Per candidate, the score on each instance relative to the best score of
+    // the instance: (score - best) / |best|.
+    let mut relative = vec![vec![]; candidates.len()];
+    for (k, &(id, seed)) in instances.iter().enumerate() {
+        if alive.len() == 1 {
+            break;
+        }
+        let scores = alive
+            .par_iter()
+            .map(|&c| run(race, &candidates[c], id, seed))
+            .collect::<Result<Vec<_>>>()?;
+        let best = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        for (&c, score) in alive.iter().zip(&scores) {
+            relative[c].push((score - best) / best.abs().max(1.0));
+        }
+
+        let mean = |c: usize| relative[c].iter().sum::<f64>() / relative[c].len() as f64;
+        let leader = *alive
+            .iter()
+            .max_by_key(|&&c| OrderedFloat(mean(c)))
+            .unwrap();
+        if k + 1 >= MIN_INSTANCES {
+            alive.retain(|&c| c == leader || !worse(&relative[c], &relative[leader]));
+        }
+        println!(
+            "# instance {}/{}: problem {id}, seed {seed}, alive: {}, leader: {}",
+            k + 1,
+            instances.len(),
+            alive.len(),
+            serde_json::to_string(&candidates[leader])?
+        );
+    }
+    let mean = |c: usize| relative[c].iter().sum::<f64>() / relative[c].len().max(1) as f64;
+    Ok(*alive
+        .iter()
+        .max_by_key(|&&c| OrderedFloat(mean(c)))
+        .unwrap())
+}
+
+/// Races per class of the problems, and saves the winners in stats/tuned.json.
+pub fn tune(race: &Race, jobs: usize) -> Result<BTreeMap<String, Params>> {
+    let candidates = race.candidates()?;
+    ensure!(!candidates.is_empty(), "no candidates");
+    let mut classes: BTreeMap<Class, Vec<ProblemId>> = BTreeMap::new();
+    for &id in &race.problems {
+        let class = Class::new(id.into(), &Problem::new(id)?);
+        classes.entry(class).or_default().push(id);
+    }
+
+    let mut winners = BTreeMap::new();
+    for (class, ids) in classes {
+        // Different problems come first.
+        let instances = race
+            .seeds
+            .iter()
+            .flat_map(|&seed| ids.iter().map(move |&id| (id, seed)))
+            .collect::<Vec<_>>();
+        println!(
+            "# {class}: {} candidates, {} instances",
+            candidates.len(),
+            instances.len()
+        );
+        let winner = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()?
+            .install(|| race_class(race, &candidates, &instances))?;
+        println!("{class} {}", serde_json::to_string(&candidates[winner])?);
+        winners.insert(class.to_string(), candidates[winner].clone());
+    }
+
+    let mut tuned = read_tuned()?;
+    tuned.extend(winners.clone());
+    write_atomic(TUNED, &serde_json::to_string_pretty(&tuned)?)?;
+    Ok(winners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worse_test() {
+        assert!(!worse(&[0.0], &[1.0]));
+        assert!(worse(&[0.0, 0.0, 0.0], &[1.0, 1.0, 1.0]));
+        assert!(!worse(&[1.0, 1.0, 1.0], &[0.0, 0.0, 0.0]));
+        assert!(worse(&[0.0, 0.1, 0.0, 0.1], &[1.0, 1.0, 1.1, 1.0]));
+        assert!(!worse(&[0.0, 1.0, 0.0, 1.0], &[1.0, 0.0, 1.0, 0.0]));
+        assert!(!worse(&[-1e-12, -1e-12, -1e-12], &[0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn candidates() -> Result<()> {
+        let race: Race = serde_json::from_str(
+            r#"{"problems": [42], "seeds": [0], "end": "iter-1000",
+                "grid": {"temp0": [10, null], "swap_prob": [0.1, 0.2]}}"#,
+        )?;
+        let candidates = race.candidates()?;
+        assert_eq!(candidates.len(), 4);
+        assert_eq!(candidates[0].temp0, Some(10.0));
+        assert_eq!(candidates[1].temp0, None);
+        assert_eq!(candidates[0].shift_weight, Params::default().shift_weight);
+
+        let race: Race = serde_json::from_str(
+            r#"{"problems": [42], "seeds": [0], "end": "iter-1000",
+                "grid": {"swap_prob": [2.0]}}"#,
+        )?;
+        assert!(race.candidates().is_err());
+        Ok(())
+    }
+}